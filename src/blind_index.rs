@@ -0,0 +1,163 @@
+//! Blind indexes for equality queries over encrypted columns
+//!
+//! A blind index is a truncated [`NekoMac`] of a value under a key derived
+//! per column from a master key, stored next to the ciphertext. Looking up a
+//! value means computing its index and querying for matches, then decrypting
+//! the candidates to weed out false positives. Fewer bits leak less about the
+//! plaintext but match more unrelated rows.
+
+use std::fmt;
+use crate::{KawaiiHash, NekoError, NekoMac, NekoResult, Version, hkdf};
+
+/// Largest supported index size in bits, the full MAC output
+pub const MAX_BITS: usize = 256;
+
+const SINGLE_TAG: u8 = 0;
+const COMPOUND_TAG: u8 = 1;
+
+/// Index generator for one column or compound of columns
+#[derive(Clone)]
+pub struct BlindIndex {
+    mac: NekoMac<KawaiiHash>,
+    bits: usize,
+}
+
+impl BlindIndex {
+    /// Derives the index key for `column` from a 32-byte master key and
+    /// truncates indexes to `bits` bits
+    pub fn new(master_key: &[u8], column: &str, bits: usize) -> NekoResult<Self> {
+        if master_key.len() != 32 {
+            return Err(NekoError::KeyError("Master key must be exactly 32 bytes".into()));
+        }
+        if !(1..=MAX_BITS).contains(&bits) {
+            return Err(NekoError::InvalidInput(format!("Index size must be between 1 and {} bits", MAX_BITS)));
+        }
+
+        let hash = KawaiiHash::with_version(Version::V2);
+        let mut info = b"nekohash.blind_index.".to_vec();
+        info.extend_from_slice(column.as_bytes());
+        let key = hkdf::derive(&hash, &[], master_key, &info, 32)?;
+
+        Ok(Self {
+            mac: NekoMac::new(hash, &key)?,
+            bits,
+        })
+    }
+
+    /// Returns the index size in bits
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// Returns the chance that an unrelated value shares a given index
+    pub fn false_positive_rate(&self) -> f64 {
+        0.5f64.powi(self.bits as i32)
+    }
+
+    /// Computes the index of a raw value
+    pub fn index(&self, value: &[u8]) -> Vec<u8> {
+        let mut mac = self.mac.clone();
+        mac.update(&[SINGLE_TAG]);
+        mac.update(value);
+        self.truncate(mac.finalize())
+    }
+
+    /// Computes the index of a value after [`normalize`]
+    pub fn index_normalized(&self, value: &str) -> Vec<u8> {
+        self.index(normalize(value).as_bytes())
+    }
+
+    /// Computes one index over several normalized values, e.g. first and last name
+    ///
+    /// Values are length-prefixed, so `["ab", "c"]` and `["a", "bc"]` differ.
+    pub fn index_compound(&self, values: &[&str]) -> Vec<u8> {
+        let mut mac = self.mac.clone();
+        mac.update(&[COMPOUND_TAG]);
+        mac.update(&(values.len() as u64).to_le_bytes());
+        for value in values {
+            let value = normalize(value);
+            mac.update(&(value.len() as u64).to_le_bytes());
+            mac.update(value.as_bytes());
+        }
+        self.truncate(mac.finalize())
+    }
+
+    /// Keeps the leading `bits` bits, zeroing the rest of the last byte
+    fn truncate(&self, mut digest: Vec<u8>) -> Vec<u8> {
+        digest.truncate(self.bits.div_ceil(8));
+        let spare = digest.len() * 8 - self.bits;
+        if let Some(last) = digest.last_mut() {
+            *last &= 0xFF << spare;
+        }
+        digest
+    }
+}
+
+impl fmt::Debug for BlindIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlindIndex")
+            .field("bits", &self.bits)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Trims surrounding whitespace and lowercases a value before indexing
+pub fn normalize(value: &str) -> String {
+    value.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: [u8; 32] = [42; 32];
+
+    #[test]
+    fn test_index_is_deterministic_per_column() {
+        let email = BlindIndex::new(&MASTER, "users.email", 32).unwrap();
+        let a = email.index(b"alice@example.com");
+        assert_eq!(a.len(), 4);
+        assert_eq!(a, email.index(b"alice@example.com"));
+        assert_ne!(a, email.index(b"bob@example.com"));
+
+        let other_column = BlindIndex::new(&MASTER, "users.backup_email", 32).unwrap();
+        assert_ne!(a, other_column.index(b"alice@example.com"));
+        let other_key = BlindIndex::new(&[7u8; 32], "users.email", 32).unwrap();
+        assert_ne!(a, other_key.index(b"alice@example.com"));
+    }
+
+    #[test]
+    fn test_truncation() {
+        let full = BlindIndex::new(&MASTER, "c", MAX_BITS).unwrap().index(b"value");
+        assert_eq!(full.len(), 32);
+
+        for bits in [1, 7, 8, 12, 64] {
+            let index = BlindIndex::new(&MASTER, "c", bits).unwrap().index(b"value");
+            assert_eq!(index.len(), bits.div_ceil(8));
+            // Truncation keeps a prefix of the full index
+            let spare = index.len() * 8 - bits;
+            assert_eq!(index[..index.len() - 1], full[..index.len() - 1]);
+            assert_eq!(index[index.len() - 1], full[index.len() - 1] & (0xFF << spare));
+        }
+
+        let index = BlindIndex::new(&MASTER, "c", 16).unwrap();
+        assert_eq!(index.false_positive_rate(), 1.0 / 65536.0);
+
+        assert!(matches!(BlindIndex::new(&MASTER, "c", 0), Err(NekoError::InvalidInput(_))));
+        assert!(matches!(BlindIndex::new(&MASTER, "c", MAX_BITS + 1), Err(NekoError::InvalidInput(_))));
+        assert!(matches!(BlindIndex::new(&MASTER[..16], "c", 16), Err(NekoError::KeyError(_))));
+    }
+
+    #[test]
+    fn test_normalized_and_compound() {
+        let index = BlindIndex::new(&MASTER, "users.name", 64).unwrap();
+        assert_eq!(normalize("  Alice Smith\n"), "alice smith");
+        assert_eq!(index.index_normalized("  ALICE "), index.index(b"alice"));
+
+        let compound = index.index_compound(&["Alice ", " SMITH"]);
+        assert_eq!(compound, index.index_compound(&["alice", "smith"]));
+        assert_ne!(compound, index.index_compound(&["alices", "mith"]));
+        assert_ne!(compound, index.index_compound(&["smith", "alice"]));
+        assert_ne!(index.index_compound(&["alice"]), index.index(b"alice"));
+    }
+}
@@ -0,0 +1,306 @@
+//! RustCrypto [`digest`] trait implementations, enabled by the `digest` feature
+//!
+//! `TsundereHasher`, `MagicalHasher` and the Kawaii types start from the
+//! default [`Version::V1`] configuration, whose absorption lets inputs such as
+//! `m` and `m || 0x00` collide. They are unsuitable for HMAC, HKDF or any other
+//! keyed construction; use [`TsundereV2Hasher`] or [`MagicalV2Hasher`] there.
+
+use std::marker::PhantomData;
+use digest::{
+    ExtendableOutput, ExtendableOutputReset, FixedOutput, FixedOutputReset, HashMarker,
+    InvalidBufferSize, InvalidOutputSize, Output, OutputSizeUser, Reset, Update, VariableOutput,
+    VariableOutputReset, XofReader,
+    core_api::BlockSizeUser,
+    generic_array::ArrayLength,
+    typenum::{U16, U32, U64},
+};
+use crate::{
+    KawaiiHash, KawaiiHasher, KawaiiXof, MagicalHash, MagicalHasher, NekoHash, NekoHasher,
+    TsundereHash, TsundereHasher, Version,
+};
+
+macro_rules! impl_fixed_digest {
+    ($hasher:ty, $size:ty) => {
+        impl HashMarker for $hasher {}
+
+        impl BlockSizeUser for $hasher {
+            type BlockSize = U64;
+        }
+
+        impl OutputSizeUser for $hasher {
+            type OutputSize = $size;
+        }
+
+        impl Update for $hasher {
+            fn update(&mut self, data: &[u8]) {
+                NekoHasher::update(self, data);
+            }
+        }
+
+        impl FixedOutput for $hasher {
+            fn finalize_into(self, out: &mut Output<Self>) {
+                NekoHasher::finalize_into(self, out);
+            }
+        }
+
+        impl Reset for $hasher {
+            fn reset(&mut self) {
+                NekoHasher::reset(self);
+            }
+        }
+
+        impl FixedOutputReset for $hasher {
+            fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+                NekoHasher::finalize_into(self.clone(), out);
+                NekoHasher::reset(self);
+            }
+        }
+    };
+}
+
+impl_fixed_digest!(TsundereHasher, U32);
+impl_fixed_digest!(MagicalHasher, U16);
+
+macro_rules! v2_digest {
+    ($(#[$doc:meta])* $name:ident, $hash:ty, $hasher:ty, $size:ty) => {
+        $(#[$doc])*
+        #[derive(Clone, Debug)]
+        pub struct $name($hasher);
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self(<$hash>::with_version(Version::V2).hasher())
+            }
+        }
+
+        impl NekoHasher for $name {
+            fn update(&mut self, data: &[u8]) {
+                NekoHasher::update(&mut self.0, data);
+            }
+
+            fn finalize_into(self, out: &mut [u8]) {
+                NekoHasher::finalize_into(self.0, out);
+            }
+
+            fn output_size(&self) -> usize {
+                NekoHasher::output_size(&self.0)
+            }
+
+            fn reset(&mut self) {
+                NekoHasher::reset(&mut self.0);
+            }
+        }
+
+        impl_fixed_digest!($name, $size);
+    };
+}
+
+v2_digest!(
+    /// v2 TsundereHash as a [`digest::Digest`], safe to use with `hmac` and `hkdf`
+    TsundereV2Hasher, TsundereHash, TsundereHasher, U32
+);
+v2_digest!(
+    /// v2 MagicalHash as a [`digest::Digest`], safe to use with `hmac` and `hkdf`
+    MagicalV2Hasher, MagicalHash, MagicalHasher, U16
+);
+
+impl HashMarker for KawaiiHasher {}
+
+impl Update for KawaiiHasher {
+    fn update(&mut self, data: &[u8]) {
+        NekoHasher::update(self, data);
+    }
+}
+
+impl Reset for KawaiiHasher {
+    fn reset(&mut self) {
+        NekoHasher::reset(self);
+    }
+}
+
+impl VariableOutput for KawaiiHasher {
+    const MAX_OUTPUT_SIZE: usize = usize::MAX;
+
+    fn new(output_size: usize) -> Result<Self, InvalidOutputSize> {
+        if output_size == 0 {
+            return Err(InvalidOutputSize);
+        }
+        Ok(KawaiiHash::with_size(output_size).hasher())
+    }
+
+    fn output_size(&self) -> usize {
+        NekoHasher::output_size(self)
+    }
+
+    fn finalize_variable(self, out: &mut [u8]) -> Result<(), InvalidBufferSize> {
+        if out.len() != NekoHasher::output_size(&self) {
+            return Err(InvalidBufferSize);
+        }
+        NekoHasher::finalize_into(self, out);
+        Ok(())
+    }
+}
+
+impl VariableOutputReset for KawaiiHasher {
+    fn finalize_variable_reset(&mut self, out: &mut [u8]) -> Result<(), InvalidBufferSize> {
+        self.clone().finalize_variable(out)?;
+        NekoHasher::reset(self);
+        Ok(())
+    }
+}
+
+impl ExtendableOutput for KawaiiHasher {
+    type Reader = KawaiiXof;
+
+    fn finalize_xof(self) -> KawaiiXof {
+        KawaiiHasher::finalize_xof(self)
+    }
+}
+
+impl ExtendableOutputReset for KawaiiHasher {
+    fn finalize_xof_reset(&mut self) -> KawaiiXof {
+        let reader = self.clone().finalize_xof();
+        NekoHasher::reset(self);
+        reader
+    }
+}
+
+impl XofReader for KawaiiXof {
+    fn read(&mut self, buffer: &mut [u8]) {
+        self.squeeze(buffer);
+    }
+}
+
+/// KawaiiHash with its output size fixed at compile time by a `typenum` length,
+/// so it can be used wherever a [`digest::Digest`] is expected
+#[derive(Clone, Debug)]
+pub struct SizedKawaiiHasher<N: ArrayLength<u8> + 'static> {
+    inner: KawaiiHasher,
+    size: PhantomData<N>,
+}
+
+/// KawaiiHash with a 32-byte output
+pub type Kawaii256 = SizedKawaiiHasher<U32>;
+
+/// KawaiiHash with a 64-byte output
+pub type Kawaii512 = SizedKawaiiHasher<U64>;
+
+impl<N: ArrayLength<u8> + 'static> SizedKawaiiHasher<N> {
+    /// Creates a sized hasher with the specified seed
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            inner: KawaiiHash::with_size_and_seed(N::USIZE, seed).hasher(),
+            size: PhantomData,
+        }
+    }
+}
+
+impl<N: ArrayLength<u8> + 'static> Default for SizedKawaiiHasher<N> {
+    fn default() -> Self {
+        Self {
+            inner: KawaiiHash::with_size(N::USIZE).hasher(),
+            size: PhantomData,
+        }
+    }
+}
+
+impl<N: ArrayLength<u8> + 'static> HashMarker for SizedKawaiiHasher<N> {}
+
+impl<N: ArrayLength<u8> + 'static> BlockSizeUser for SizedKawaiiHasher<N> {
+    type BlockSize = U64;
+}
+
+impl<N: ArrayLength<u8> + 'static> OutputSizeUser for SizedKawaiiHasher<N> {
+    type OutputSize = N;
+}
+
+impl<N: ArrayLength<u8> + 'static> Update for SizedKawaiiHasher<N> {
+    fn update(&mut self, data: &[u8]) {
+        NekoHasher::update(&mut self.inner, data);
+    }
+}
+
+impl<N: ArrayLength<u8> + 'static> FixedOutput for SizedKawaiiHasher<N> {
+    fn finalize_into(self, out: &mut Output<Self>) {
+        NekoHasher::finalize_into(self.inner, out);
+    }
+}
+
+impl<N: ArrayLength<u8> + 'static> Reset for SizedKawaiiHasher<N> {
+    fn reset(&mut self) {
+        NekoHasher::reset(&mut self.inner);
+    }
+}
+
+impl<N: ArrayLength<u8> + 'static> FixedOutputReset for SizedKawaiiHasher<N> {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        NekoHasher::finalize_into(self.inner.clone(), out);
+        NekoHasher::reset(&mut self.inner);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use digest::Digest;
+    use crate::{MagicalHash, TsundereHash};
+
+    fn digest_of<D: Digest>(data: &[u8]) -> Vec<u8> {
+        let mut hasher = D::new();
+        Digest::update(&mut hasher, &data[..5]);
+        Digest::update(&mut hasher, &data[5..]);
+        hasher.finalize().to_vec()
+    }
+
+    #[test]
+    fn test_fixed_digests_match_neko_hash() {
+        let input = b"Hello, World!";
+        assert_eq!(digest_of::<TsundereHasher>(input), TsundereHash::new().hash(input));
+        assert_eq!(digest_of::<MagicalHasher>(input), MagicalHash::new().hash(input));
+        assert_eq!(digest_of::<Kawaii256>(input), KawaiiHash::new().hash(input));
+        assert_eq!(digest_of::<Kawaii512>(input), KawaiiHash::with_size(64).hash(input));
+    }
+
+    #[test]
+    fn test_v2_digests_match_neko_hash() {
+        let input = b"Hello, World!";
+        let tsundere = TsundereHash::with_version(Version::V2);
+        let magical = MagicalHash::with_version(Version::V2);
+        assert_eq!(digest_of::<TsundereV2Hasher>(input), tsundere.hash(input));
+        assert_eq!(digest_of::<MagicalV2Hasher>(input), magical.hash(input));
+        assert_ne!(digest_of::<TsundereV2Hasher>(input), digest_of::<TsundereHasher>(input));
+    }
+
+    #[test]
+    fn test_finalize_reset() {
+        let mut hasher = TsundereHasher::default();
+        Digest::update(&mut hasher, b"first");
+        let first = Digest::finalize_reset(&mut hasher);
+        Digest::update(&mut hasher, b"first");
+        assert_eq!(Digest::finalize(hasher), first);
+    }
+
+    #[test]
+    fn test_extendable_output() {
+        let mut hasher = KawaiiHasher::default();
+        Update::update(&mut hasher, b"Hello, World!");
+
+        let mut out = [0u8; 100];
+        ExtendableOutput::finalize_xof(hasher).read(&mut out);
+        assert_eq!(out.to_vec(), KawaiiHash::new().xof(b"Hello, World!").squeeze_vec(100));
+    }
+
+    #[test]
+    fn test_variable_output() {
+        let input = b"Hello, World!";
+        let mut hasher = <KawaiiHasher as VariableOutput>::new(24).unwrap();
+        Update::update(&mut hasher, input);
+
+        let mut out = [0u8; 24];
+        hasher.clone().finalize_variable(&mut out).unwrap();
+        assert_eq!(out.to_vec(), KawaiiHash::with_size(24).hash(input));
+
+        assert!(hasher.finalize_variable(&mut [0u8; 8]).is_err());
+        assert!(<KawaiiHasher as VariableOutput>::new(0).is_err());
+    }
+}
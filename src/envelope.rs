@@ -0,0 +1,436 @@
+//! Versioned, self-describing ciphertext envelope
+//!
+//! The binary layout is
+//!
+//! ```text
+//! magic "NEKO" | version u8 | suite u8 | key id length u8 | key id | nonce | ciphertext | tag
+//! ```
+//!
+//! where the nonce and tag sizes are fixed by the cipher suite. The tag covers
+//! the whole header, so the key id and suite cannot be swapped undetected.
+//! Envelopes can also be wrapped in PEM-like ASCII armor with a CRC-24 checksum.
+
+use aes::Aes256;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use ctr::{Ctr64BE, cipher::{KeyIvInit, StreamCipher}};
+use hmac::{Hmac, Mac};
+use rand::{Rng, thread_rng};
+use sha2::Sha256;
+use crate::{NekoError, NekoResult};
+
+pub(crate) type Aes256Ctr64BE = Ctr64BE<Aes256>;
+pub(crate) type HmacSha256 = Hmac<Sha256>;
+
+/// Leading bytes of every binary envelope
+pub const MAGIC: &[u8; 4] = b"NEKO";
+/// Envelope format version written by [`Envelope::seal`]
+pub const CURRENT_VERSION: u8 = 1;
+
+const ARMOR_BEGIN: &str = "-----BEGIN NEKO ENCRYPTED-----";
+const ARMOR_END: &str = "-----END NEKO ENCRYPTED-----";
+const ARMOR_LINE_WIDTH: usize = 64;
+
+const CRC24_INIT: u32 = 0xB704CE;
+const CRC24_POLY: u32 = 0x1864CFB;
+
+/// Algorithms used to encrypt and authenticate an envelope
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CipherSuite {
+    /// AES-256-CTR with an HMAC-SHA256 tag (encrypt-then-MAC)
+    Aes256CtrHmacSha256,
+}
+
+impl CipherSuite {
+    /// Returns the identifier written into the envelope header
+    pub fn id(&self) -> u8 {
+        match self {
+            CipherSuite::Aes256CtrHmacSha256 => 1,
+        }
+    }
+
+    /// Looks up a suite by its header identifier
+    pub fn from_id(id: u8) -> NekoResult<Self> {
+        match id {
+            1 => Ok(CipherSuite::Aes256CtrHmacSha256),
+            _ => Err(NekoError::EncodingError(format!("Unsupported cipher suite: {}", id))),
+        }
+    }
+
+    /// Returns the nonce size in bytes
+    pub fn nonce_size(&self) -> usize {
+        match self {
+            CipherSuite::Aes256CtrHmacSha256 => 16,
+        }
+    }
+
+    /// Returns the tag size in bytes
+    pub fn tag_size(&self) -> usize {
+        match self {
+            CipherSuite::Aes256CtrHmacSha256 => 32,
+        }
+    }
+}
+
+/// Parsed ciphertext envelope
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Envelope {
+    version: u8,
+    suite: CipherSuite,
+    key_id: Vec<u8>,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+    tag: Vec<u8>,
+}
+
+impl Envelope {
+    /// Encrypts `data` under a 32-byte key, authenticating it together with
+    /// `aad` and labelling it with `key_id` (at most 255 bytes, may be empty)
+    pub fn seal(data: &[u8], aad: &[u8], key: &[u8], key_id: &[u8]) -> NekoResult<Self> {
+        check_key(key)?;
+        if key_id.len() > u8::MAX as usize {
+            return Err(NekoError::InvalidInput("Key id must be at most 255 bytes".into()));
+        }
+
+        let suite = CipherSuite::Aes256CtrHmacSha256;
+        let mut nonce = vec![0u8; suite.nonce_size()];
+        thread_rng().fill(&mut nonce[..]);
+
+        let mut envelope = Self {
+            version: CURRENT_VERSION,
+            suite,
+            key_id: key_id.to_vec(),
+            nonce,
+            ciphertext: data.to_vec(),
+            tag: Vec::new(),
+        };
+
+        let (cipher_key, mac_key) = subkeys(key);
+        envelope.keystream(&cipher_key).apply_keystream(&mut envelope.ciphertext);
+        envelope.tag = envelope.mac(&mac_key, aad).finalize().into_bytes().to_vec();
+        Ok(envelope)
+    }
+
+    /// Verifies the tag over the header, `aad` and ciphertext, then decrypts
+    ///
+    /// Returns [`NekoError::CryptoError`] if the key or `aad` is wrong or any
+    /// part of the envelope was modified.
+    pub fn open(&self, aad: &[u8], key: &[u8]) -> NekoResult<Vec<u8>> {
+        check_key(key)?;
+
+        match (self.version, self.suite) {
+            (1, CipherSuite::Aes256CtrHmacSha256) => {
+                let (cipher_key, mac_key) = subkeys(key);
+                self.mac(&mac_key, aad)
+                    .verify_slice(&self.tag)
+                    .map_err(|_| NekoError::CryptoError("Authentication failed".into()))?;
+
+                let mut plaintext = self.ciphertext.clone();
+                self.keystream(&cipher_key).apply_keystream(&mut plaintext);
+                Ok(plaintext)
+            }
+            (version, _) => Err(NekoError::EncodingError(format!("Unsupported envelope version: {}", version))),
+        }
+    }
+
+    /// Returns the format version
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the cipher suite
+    pub fn suite(&self) -> CipherSuite {
+        self.suite
+    }
+
+    /// Returns the identifier of the key that sealed this envelope
+    pub fn key_id(&self) -> &[u8] {
+        &self.key_id
+    }
+
+    /// Returns the nonce
+    pub fn nonce(&self) -> &[u8] {
+        &self.nonce
+    }
+
+    /// Returns the ciphertext without header or tag
+    pub fn ciphertext(&self) -> &[u8] {
+        &self.ciphertext
+    }
+
+    /// Returns the authentication tag
+    pub fn tag(&self) -> &[u8] {
+        &self.tag
+    }
+
+    /// Serializes the envelope to its binary layout
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header();
+        bytes.extend_from_slice(&self.ciphertext);
+        bytes.extend_from_slice(&self.tag);
+        bytes
+    }
+
+    /// Parses an envelope from its binary layout
+    pub fn from_bytes(bytes: &[u8]) -> NekoResult<Self> {
+        let truncated = || NekoError::EncodingError("Truncated envelope".into());
+
+        let rest = bytes
+            .strip_prefix(MAGIC.as_slice())
+            .ok_or_else(|| NekoError::EncodingError("Missing envelope magic".into()))?;
+        let (&version, rest) = rest.split_first().ok_or_else(truncated)?;
+        if version != CURRENT_VERSION {
+            return Err(NekoError::EncodingError(format!("Unsupported envelope version: {}", version)));
+        }
+        let (&suite, rest) = rest.split_first().ok_or_else(truncated)?;
+        let suite = CipherSuite::from_id(suite)?;
+        let (&key_id_len, rest) = rest.split_first().ok_or_else(truncated)?;
+
+        let key_id_len = key_id_len as usize;
+        if rest.len() < key_id_len + suite.nonce_size() + suite.tag_size() {
+            return Err(truncated());
+        }
+        let (key_id, rest) = rest.split_at(key_id_len);
+        let (nonce, rest) = rest.split_at(suite.nonce_size());
+        let (ciphertext, tag) = rest.split_at(rest.len() - suite.tag_size());
+
+        Ok(Self {
+            version,
+            suite,
+            key_id: key_id.to_vec(),
+            nonce: nonce.to_vec(),
+            ciphertext: ciphertext.to_vec(),
+            tag: tag.to_vec(),
+        })
+    }
+
+    /// Serializes the envelope as ASCII armor
+    pub fn to_armor(&self) -> String {
+        armor(&self.to_bytes())
+    }
+
+    /// Parses an envelope from ASCII armor, checking the CRC-24 checksum
+    pub fn from_armor(text: &str) -> NekoResult<Self> {
+        Self::from_bytes(&dearmor(text)?)
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(7 + self.key_id.len() + self.nonce.len());
+        header.extend_from_slice(MAGIC);
+        header.push(self.version);
+        header.push(self.suite.id());
+        header.push(self.key_id.len() as u8);
+        header.extend_from_slice(&self.key_id);
+        header.extend_from_slice(&self.nonce);
+        header
+    }
+
+    fn keystream(&self, cipher_key: &[u8; 32]) -> Aes256Ctr64BE {
+        Aes256Ctr64BE::new(cipher_key.into(), self.nonce[..].into())
+    }
+
+    fn mac(&self, mac_key: &[u8; 32], aad: &[u8]) -> HmacSha256 {
+        let mut mac = hmac_sha256(mac_key);
+        mac.update(&self.header());
+        mac.update(&(aad.len() as u64).to_le_bytes());
+        mac.update(aad);
+        mac.update(&self.ciphertext);
+        mac
+    }
+}
+
+pub(crate) fn check_key(key: &[u8]) -> NekoResult<()> {
+    if key.len() != 32 {
+        return Err(NekoError::KeyError("Key must be exactly 32 bytes".into()));
+    }
+    Ok(())
+}
+
+pub(crate) fn hmac_sha256(key: &[u8]) -> HmacSha256 {
+    <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC accepts any key length")
+}
+
+/// Derives an independent 32-byte subkey for the purpose named by `label`
+pub(crate) fn derive_subkey(key: &[u8], label: &[u8]) -> [u8; 32] {
+    let mut mac = hmac_sha256(key);
+    mac.update(label);
+    mac.finalize().into_bytes().into()
+}
+
+/// Splits a 32-byte key into independent cipher and MAC keys
+fn subkeys(key: &[u8]) -> ([u8; 32], [u8; 32]) {
+    (derive_subkey(key, b"nekohash.aead.cipher"), derive_subkey(key, b"nekohash.aead.mac"))
+}
+
+/// Wraps bytes in `NEKO ENCRYPTED` armor: base64 lines of 64 characters
+/// followed by a `=`-prefixed base64 CRC-24 of the bytes
+pub fn armor(bytes: &[u8]) -> String {
+    let encoded = BASE64.encode(bytes);
+    let crc = crc24(bytes).to_be_bytes();
+
+    let mut text = String::with_capacity(encoded.len() + encoded.len() / ARMOR_LINE_WIDTH + 80);
+    text.push_str(ARMOR_BEGIN);
+    text.push('\n');
+    for line in encoded.as_bytes().chunks(ARMOR_LINE_WIDTH) {
+        // Base64 output is ASCII, so any chunk is valid UTF-8
+        text.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        text.push('\n');
+    }
+    text.push('=');
+    text.push_str(&BASE64.encode(&crc[1..]));
+    text.push('\n');
+    text.push_str(ARMOR_END);
+    text.push('\n');
+    text
+}
+
+/// Extracts and checksums the bytes inside `NEKO ENCRYPTED` armor
+///
+/// Surrounding text and blank lines are ignored.
+pub fn dearmor(text: &str) -> NekoResult<Vec<u8>> {
+    let mut lines = text.lines().map(str::trim).skip_while(|line| *line != ARMOR_BEGIN);
+    if lines.next().is_none() {
+        return Err(NekoError::EncodingError("Missing armor header".into()));
+    }
+
+    let mut body = String::new();
+    let mut checksum = None;
+    let mut terminated = false;
+    for line in lines {
+        if line == ARMOR_END {
+            terminated = true;
+            break;
+        }
+        if let Some(crc) = line.strip_prefix('=') {
+            checksum = Some(crc);
+        } else if checksum.is_none() {
+            body.push_str(line);
+        } else if !line.is_empty() {
+            return Err(NekoError::EncodingError("Unexpected data after armor checksum".into()));
+        }
+    }
+    if !terminated {
+        return Err(NekoError::EncodingError("Missing armor footer".into()));
+    }
+
+    let bytes = BASE64.decode(&body)
+        .map_err(|e| NekoError::EncodingError(format!("Invalid base64: {}", e)))?;
+    let checksum = checksum.ok_or_else(|| NekoError::EncodingError("Missing armor checksum".into()))?;
+    let checksum = BASE64.decode(checksum)
+        .ok()
+        .filter(|crc| crc.len() == 3)
+        .ok_or_else(|| NekoError::EncodingError("Invalid armor checksum".into()))?;
+    if crc24(&bytes).to_be_bytes()[1..] != checksum[..] {
+        return Err(NekoError::EncodingError("Armor checksum mismatch".into()));
+    }
+    Ok(bytes)
+}
+
+/// CRC-24 as used by OpenPGP armor (RFC 4880)
+fn crc24(data: &[u8]) -> u32 {
+    let mut crc = CRC24_INIT;
+    for &byte in data {
+        crc ^= (byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x100_0000 != 0 {
+                crc ^= CRC24_POLY;
+            }
+        }
+    }
+    crc & 0xFF_FFFF
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [42; 32];
+
+    #[test]
+    fn test_seal_open_round_trip() {
+        let envelope = Envelope::seal(b"test data", b"aad", &KEY, b"key-2024").unwrap();
+        assert_eq!(envelope.version(), CURRENT_VERSION);
+        assert_eq!(envelope.suite(), CipherSuite::Aes256CtrHmacSha256);
+        assert_eq!(envelope.key_id(), b"key-2024");
+        assert_eq!(envelope.open(b"aad", &KEY).unwrap(), b"test data");
+
+        let bytes = envelope.to_bytes();
+        assert!(bytes.starts_with(b"NEKO\x01\x01\x08key-2024"));
+        assert_eq!(bytes.len(), 7 + 8 + 16 + 9 + 32);
+        assert_eq!(Envelope::from_bytes(&bytes).unwrap(), envelope);
+
+        assert!(matches!(envelope.open(b"aad", &[7u8; 32]), Err(NekoError::CryptoError(_))));
+        assert!(matches!(envelope.open(b"other", &KEY), Err(NekoError::CryptoError(_))));
+        assert!(matches!(envelope.open(b"aad", &[7u8; 16]), Err(NekoError::KeyError(_))));
+        assert!(Envelope::seal(b"", b"", &KEY, &[0u8; 256]).is_err());
+    }
+
+    #[test]
+    fn test_header_is_authenticated() {
+        let bytes = Envelope::seal(b"test data", b"", &KEY, b"a").unwrap().to_bytes();
+
+        // Swapping the key id keeps the layout valid but breaks the tag
+        let mut swapped = bytes.clone();
+        swapped[7] = b'b';
+        let swapped = Envelope::from_bytes(&swapped).unwrap();
+        assert!(matches!(swapped.open(b"", &KEY), Err(NekoError::CryptoError(_))));
+
+        for i in [8, 30, bytes.len() - 1] {
+            let mut tampered = bytes.clone();
+            tampered[i] ^= 1;
+            let tampered = Envelope::from_bytes(&tampered).unwrap();
+            assert!(matches!(tampered.open(b"", &KEY), Err(NekoError::CryptoError(_))));
+        }
+    }
+
+    #[test]
+    fn test_malformed_envelopes() {
+        let bytes = Envelope::seal(b"", b"", &KEY, b"").unwrap().to_bytes();
+        assert!(Envelope::from_bytes(&bytes).is_ok());
+        assert!(Envelope::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Envelope::from_bytes(b"NEKO").is_err());
+        assert!(Envelope::from_bytes(b"").is_err());
+
+        for (i, value) in [(0, b'X'), (4, 2), (5, 9)] {
+            let mut bad = bytes.clone();
+            bad[i] = value;
+            assert!(matches!(Envelope::from_bytes(&bad), Err(NekoError::EncodingError(_))));
+        }
+    }
+
+    #[test]
+    fn test_crc24() {
+        assert_eq!(crc24(b""), CRC24_INIT);
+        assert_eq!(crc24(b"123456789"), 0x21CF02);
+    }
+
+    #[test]
+    fn test_armor_round_trip() {
+        let envelope = Envelope::seal(&[0xAB; 200], b"", &KEY, b"k1").unwrap();
+        let text = envelope.to_armor();
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], ARMOR_BEGIN);
+        assert_eq!(*lines.last().unwrap(), ARMOR_END);
+        assert!(lines[1..lines.len() - 2].iter().all(|line| line.len() <= ARMOR_LINE_WIDTH));
+        assert_eq!(lines[lines.len() - 2].len(), 5);
+
+        let wrapped = format!("Subject: hi\n\n{}\nbye\n", text.replace('\n', "\r\n"));
+        assert_eq!(Envelope::from_armor(&wrapped).unwrap(), envelope);
+        assert_eq!(Envelope::from_armor(&text).unwrap().open(b"", &KEY).unwrap(), vec![0xAB; 200]);
+    }
+
+    #[test]
+    fn test_armor_errors() {
+        let text = armor(b"hello armor");
+        assert_eq!(dearmor(&text).unwrap(), b"hello armor");
+
+        let corrupted = text.replacen("aGVsbG8", "aGVsbG9", 1);
+        assert!(dearmor(&corrupted).unwrap_err().to_string().contains("checksum"));
+
+        let no_checksum: String = text.lines().filter(|l| !l.starts_with('=')).map(|l| format!("{}\n", l)).collect();
+        assert!(dearmor(&no_checksum).is_err());
+        assert!(dearmor(&text.replace(ARMOR_END, "")).is_err());
+        assert!(dearmor("aGVsbG8=").is_err());
+    }
+}
@@ -0,0 +1,421 @@
+//! Format-preserving encryption with NIST FF1 (SP 800-38G)
+//!
+//! Ciphertexts have the same length and alphabet as their plaintexts, so
+//! encrypted card numbers or fixed-format IDs still pass downstream format
+//! validation. The key size selects AES-128, AES-192 or AES-256.
+
+use std::fmt;
+use aes::{Aes128, Aes192, Aes256};
+use aes::cipher::{BlockEncrypt, KeyInit};
+use crate::{NekoError, NekoResult};
+
+/// Alphabet used by [`Ff1::new`] for radixes up to 36
+const DEFAULT_ALPHABET: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Smallest domain size allowed by SP 800-38G Rev. 1
+const MIN_DOMAIN: u64 = 1_000_000;
+const MAX_RADIX: u32 = 1 << 16;
+const FEISTEL_ROUNDS: u8 = 10;
+
+#[derive(Clone)]
+enum BlockCipher {
+    Aes128(Aes128),
+    Aes192(Aes192),
+    Aes256(Aes256),
+}
+
+impl BlockCipher {
+    fn new(key: &[u8]) -> NekoResult<Self> {
+        match key.len() {
+            16 => Ok(BlockCipher::Aes128(Aes128::new(key.into()))),
+            24 => Ok(BlockCipher::Aes192(Aes192::new(key.into()))),
+            32 => Ok(BlockCipher::Aes256(Aes256::new(key.into()))),
+            _ => Err(NekoError::KeyError("Key must be 16, 24 or 32 bytes".into())),
+        }
+    }
+
+    fn encrypt_block(&self, block: &mut [u8; 16]) {
+        let block = block.into();
+        match self {
+            BlockCipher::Aes128(cipher) => cipher.encrypt_block(block),
+            BlockCipher::Aes192(cipher) => cipher.encrypt_block(block),
+            BlockCipher::Aes256(cipher) => cipher.encrypt_block(block),
+        }
+    }
+}
+
+/// FF1 cipher over a fixed radix and alphabet
+#[derive(Clone)]
+pub struct Ff1 {
+    cipher: BlockCipher,
+    radix: u32,
+    alphabet: Vec<char>,
+}
+
+impl Ff1 {
+    /// Creates a cipher over digits `0..radix`
+    ///
+    /// For radixes up to 36 the string methods use the alphabet
+    /// `0-9a-z` truncated to `radix` characters.
+    pub fn new(key: &[u8], radix: u32) -> NekoResult<Self> {
+        if !(2..=MAX_RADIX).contains(&radix) {
+            return Err(NekoError::InvalidInput(format!("Radix must be between 2 and {}", MAX_RADIX)));
+        }
+        Ok(Self {
+            cipher: BlockCipher::new(key)?,
+            radix,
+            alphabet: if radix as usize <= DEFAULT_ALPHABET.len() {
+                DEFAULT_ALPHABET.chars().take(radix as usize).collect()
+            } else {
+                Vec::new()
+            },
+        })
+    }
+
+    /// Creates a cipher whose radix is the number of characters in `alphabet`
+    pub fn with_alphabet(key: &[u8], alphabet: &str) -> NekoResult<Self> {
+        let chars: Vec<char> = alphabet.chars().collect();
+        if chars.iter().enumerate().any(|(i, c)| chars[..i].contains(c)) {
+            return Err(NekoError::InvalidInput("Alphabet characters must be unique".into()));
+        }
+        let radix = u32::try_from(chars.len()).unwrap_or(u32::MAX);
+        Ok(Self { alphabet: chars, ..Self::new(key, radix)? })
+    }
+
+    /// Returns the radix
+    pub fn radix(&self) -> u32 {
+        self.radix
+    }
+
+    /// Encrypts a string over the cipher's alphabet
+    pub fn encrypt(&self, plaintext: &str, tweak: &[u8]) -> NekoResult<String> {
+        let digits = self.encrypt_digits(&self.parse_numeral(plaintext)?, tweak)?;
+        Ok(self.format_numeral(&digits))
+    }
+
+    /// Decrypts a string over the cipher's alphabet
+    pub fn decrypt(&self, ciphertext: &str, tweak: &[u8]) -> NekoResult<String> {
+        let digits = self.decrypt_digits(&self.parse_numeral(ciphertext)?, tweak)?;
+        Ok(self.format_numeral(&digits))
+    }
+
+    /// Encrypts a numeral string given as digits in `0..radix`
+    pub fn encrypt_digits(&self, digits: &[u32], tweak: &[u8]) -> NekoResult<Vec<u32>> {
+        let rounds = self.rounds(digits, tweak)?;
+        let (mut a, mut b) = {
+            let (a, b) = digits.split_at(rounds.u);
+            (a.to_vec(), b.to_vec())
+        };
+
+        for i in 0..FEISTEL_ROUNDS {
+            let y = self.round_value(&rounds, tweak, i, &b);
+            let m = if i % 2 == 0 { rounds.u } else { rounds.v };
+            let c = add_mod(&a, &self.reduce(&y, m), self.radix);
+            a = b;
+            b = c;
+        }
+
+        a.extend_from_slice(&b);
+        Ok(a)
+    }
+
+    /// Decrypts a numeral string given as digits in `0..radix`
+    pub fn decrypt_digits(&self, digits: &[u32], tweak: &[u8]) -> NekoResult<Vec<u32>> {
+        let rounds = self.rounds(digits, tweak)?;
+        let (mut a, mut b) = {
+            let (a, b) = digits.split_at(rounds.u);
+            (a.to_vec(), b.to_vec())
+        };
+
+        for i in (0..FEISTEL_ROUNDS).rev() {
+            let y = self.round_value(&rounds, tweak, i, &a);
+            let m = if i % 2 == 0 { rounds.u } else { rounds.v };
+            let c = sub_mod(&b, &self.reduce(&y, m), self.radix);
+            b = a;
+            a = c;
+        }
+
+        a.extend_from_slice(&b);
+        Ok(a)
+    }
+
+    /// Validates the input and computes the per-message constants
+    fn rounds(&self, digits: &[u32], tweak: &[u8]) -> NekoResult<Rounds> {
+        let n = digits.len();
+        if n < 2 || domain_size(self.radix, n) < MIN_DOMAIN {
+            return Err(NekoError::InvalidInput(format!(
+                "Input too short: radix^length must be at least {}",
+                MIN_DOMAIN
+            )));
+        }
+        let n32 = u32::try_from(n).map_err(|_| NekoError::InvalidInput("Input too long".into()))?;
+        let t32 = u32::try_from(tweak.len()).map_err(|_| NekoError::InvalidInput("Tweak too long".into()))?;
+        if let Some(&digit) = digits.iter().find(|&&d| d >= self.radix) {
+            return Err(NekoError::InvalidInput(format!("Digit {} is out of range for radix {}", digit, self.radix)));
+        }
+
+        let u = n / 2;
+        let v = n - u;
+        let b = radix_power_bits(self.radix, v).div_ceil(8);
+        let d = 4 * b.div_ceil(4) + 4;
+
+        let mut p = [0u8; 16];
+        p[..3].copy_from_slice(&[1, 2, 1]);
+        p[3..6].copy_from_slice(&self.radix.to_be_bytes()[1..]);
+        p[6] = 10;
+        p[7] = u as u8;
+        p[8..12].copy_from_slice(&n32.to_be_bytes());
+        p[12..].copy_from_slice(&t32.to_be_bytes());
+
+        let mut prefix = p;
+        self.cipher.encrypt_block(&mut prefix);
+
+        Ok(Rounds { u, v, b, d, prefix })
+    }
+
+    /// Computes the round value `S` from the half `x` (steps 6.i to 6.iii)
+    fn round_value(&self, rounds: &Rounds, tweak: &[u8], i: u8, x: &[u32]) -> Vec<u8> {
+        let padding = (16 - (tweak.len() + rounds.b + 1) % 16) % 16;
+        let mut q = Vec::with_capacity(tweak.len() + padding + 1 + rounds.b);
+        q.extend_from_slice(tweak);
+        q.resize(tweak.len() + padding, 0);
+        q.push(i);
+        q.extend_from_slice(&digits_to_bytes(x, self.radix, rounds.b));
+
+        // CBC-MAC continuing from the encrypted P block
+        let mut r = rounds.prefix;
+        for block in q.chunks_exact(16) {
+            for (r, q) in r.iter_mut().zip(block) {
+                *r ^= q;
+            }
+            self.cipher.encrypt_block(&mut r);
+        }
+
+        let mut s = Vec::with_capacity(rounds.d.div_ceil(16) * 16);
+        s.extend_from_slice(&r);
+        for j in 1..rounds.d.div_ceil(16) as u128 {
+            let mut block = (u128::from_be_bytes(r) ^ j).to_be_bytes();
+            self.cipher.encrypt_block(&mut block);
+            s.extend_from_slice(&block);
+        }
+        s.truncate(rounds.d);
+        s
+    }
+
+    /// Returns `NUM(y) mod radix^m` as `m` digits
+    fn reduce(&self, y: &[u8], m: usize) -> Vec<u32> {
+        let radix = self.radix as u64;
+        let mut n = y.to_vec();
+        let mut digits = vec![0u32; m];
+        for slot in digits.iter_mut().rev() {
+            let mut remainder = 0u64;
+            for byte in n.iter_mut() {
+                let current = (remainder << 8) | *byte as u64;
+                *byte = (current / radix) as u8;
+                remainder = current % radix;
+            }
+            *slot = remainder as u32;
+        }
+        digits
+    }
+
+    fn parse_numeral(&self, text: &str) -> NekoResult<Vec<u32>> {
+        if self.alphabet.is_empty() {
+            return Err(NekoError::InvalidInput("Cipher has no alphabet; use the digit methods".into()));
+        }
+        text.chars()
+            .map(|c| {
+                self.alphabet
+                    .iter()
+                    .position(|&a| a == c)
+                    .map(|i| i as u32)
+                    .ok_or_else(|| NekoError::InvalidInput(format!("Character {:?} is not in the alphabet", c)))
+            })
+            .collect()
+    }
+
+    fn format_numeral(&self, digits: &[u32]) -> String {
+        digits.iter().map(|&d| self.alphabet[d as usize]).collect()
+    }
+}
+
+impl fmt::Debug for Ff1 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ff1")
+            .field("radix", &self.radix)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Per-message constants shared by all ten rounds
+struct Rounds {
+    u: usize,
+    v: usize,
+    b: usize,
+    d: usize,
+    /// `CIPH(P)`, the first CBC-MAC block of every round
+    prefix: [u8; 16],
+}
+
+/// Returns `radix^n`, saturating at `u64::MAX`
+fn domain_size(radix: u32, n: usize) -> u64 {
+    let mut size = 1u64;
+    for _ in 0..n {
+        size = size.saturating_mul(radix as u64);
+        if size == u64::MAX {
+            break;
+        }
+    }
+    size
+}
+
+/// Returns `ceil(v * log2(radix))`, computed exactly as the bit length of `radix^v - 1`
+fn radix_power_bits(radix: u32, v: usize) -> usize {
+    // Little-endian 32-bit limbs
+    let mut limbs = vec![1u32];
+    for _ in 0..v {
+        let mut carry = 0u64;
+        for limb in limbs.iter_mut() {
+            let product = *limb as u64 * radix as u64 + carry;
+            *limb = product as u32;
+            carry = product >> 32;
+        }
+        if carry > 0 {
+            limbs.push(carry as u32);
+        }
+    }
+
+    for limb in limbs.iter_mut() {
+        let (value, borrow) = limb.overflowing_sub(1);
+        *limb = value;
+        if !borrow {
+            break;
+        }
+    }
+    while limbs.len() > 1 && limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+
+    let top = *limbs.last().unwrap_or(&0);
+    (limbs.len() - 1) * 32 + (32 - top.leading_zeros() as usize)
+}
+
+/// Encodes `NUM_radix(digits)` as a `len`-byte big-endian integer
+fn digits_to_bytes(digits: &[u32], radix: u32, len: usize) -> Vec<u8> {
+    let mut out = vec![0u8; len];
+    for &digit in digits {
+        let mut carry = digit as u64;
+        for byte in out.iter_mut().rev() {
+            let value = *byte as u64 * radix as u64 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+    }
+    out
+}
+
+/// Digit-wise `(a + y) mod radix^m` for equal-length big-endian numerals
+fn add_mod(a: &[u32], y: &[u32], radix: u32) -> Vec<u32> {
+    let mut out = vec![0u32; a.len()];
+    let mut carry = 0u32;
+    for ((out, &a), &y) in out.iter_mut().zip(a).zip(y).rev() {
+        let sum = a as u64 + y as u64 + carry as u64;
+        carry = (sum >= radix as u64) as u32;
+        *out = (sum - carry as u64 * radix as u64) as u32;
+    }
+    out
+}
+
+/// Digit-wise `(a - y) mod radix^m` for equal-length big-endian numerals
+fn sub_mod(a: &[u32], y: &[u32], radix: u32) -> Vec<u32> {
+    let mut out = vec![0u32; a.len()];
+    let mut borrow = 0u32;
+    for ((out, &a), &y) in out.iter_mut().zip(a).zip(y).rev() {
+        let subtrahend = y as u64 + borrow as u64;
+        borrow = ((a as u64) < subtrahend) as u32;
+        *out = (a as u64 + borrow as u64 * radix as u64 - subtrahend) as u32;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::from_hex;
+
+    const KEY_128: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+    const KEY_192: &str = "2b7e151628aed2a6abf7158809cf4f3cef4359d8d580aa4f";
+    const KEY_256: &str = "2b7e151628aed2a6abf7158809cf4f3cef4359d8d580aa4f7f036d6f04fc6a94";
+    const TWEAK_DECIMAL: &str = "39383736353433323130";
+    const TWEAK_ALPHA: &str = "3737373770717273373737";
+
+    #[test]
+    fn test_nist_samples() {
+        let samples = [
+            (KEY_128, 10, "", "0123456789", "2433477484"),
+            (KEY_128, 10, TWEAK_DECIMAL, "0123456789", "6124200773"),
+            (KEY_128, 36, TWEAK_ALPHA, "0123456789abcdefghi", "a9tv40mll9kdu509eum"),
+            (KEY_192, 10, "", "0123456789", "2830668132"),
+            (KEY_192, 10, TWEAK_DECIMAL, "0123456789", "2496655549"),
+            (KEY_192, 36, TWEAK_ALPHA, "0123456789abcdefghi", "xbj3kv35jrawxv32ysr"),
+            (KEY_256, 10, "", "0123456789", "6657667009"),
+            (KEY_256, 10, TWEAK_DECIMAL, "0123456789", "1001623463"),
+            (KEY_256, 36, TWEAK_ALPHA, "0123456789abcdefghi", "xs8a0azh2avyalyzuwd"),
+        ];
+        for (key, radix, tweak, plaintext, ciphertext) in samples {
+            let ff1 = Ff1::new(&from_hex(key).unwrap(), radix).unwrap();
+            let tweak = from_hex(tweak).unwrap();
+            assert_eq!(ff1.encrypt(plaintext, &tweak).unwrap(), ciphertext);
+            assert_eq!(ff1.decrypt(ciphertext, &tweak).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_custom_alphabet_round_trip() {
+        let key = from_hex(KEY_256).unwrap();
+        let ff1 = Ff1::with_alphabet(&key, "ABCDEFGHJKLMNPQRSTUVWXYZ23456789").unwrap();
+        assert_eq!(ff1.radix(), 32);
+
+        for plaintext in ["ABCD2345", "ZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZ", "AAAA"] {
+            let ciphertext = ff1.encrypt(plaintext, b"orders.ref").unwrap();
+            assert_eq!(ciphertext.chars().count(), plaintext.chars().count());
+            assert!(ciphertext.chars().all(|c| ff1.alphabet.contains(&c)));
+            assert_ne!(ciphertext, ff1.encrypt(plaintext, b"other").unwrap());
+            assert_eq!(ff1.decrypt(&ciphertext, b"orders.ref").unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_large_radix_digits() {
+        let ff1 = Ff1::new(&from_hex(KEY_128).unwrap(), MAX_RADIX).unwrap();
+        let digits = [0, 65535, 1234, 42, 9];
+        let encrypted = ff1.encrypt_digits(&digits, b"").unwrap();
+        assert!(encrypted.iter().all(|&d| d < MAX_RADIX));
+        assert_eq!(ff1.decrypt_digits(&encrypted, b"").unwrap(), digits);
+        assert!(ff1.encrypt("abc", b"").is_err());
+    }
+
+    #[test]
+    fn test_out_of_domain_inputs() {
+        let key = from_hex(KEY_128).unwrap();
+        let ff1 = Ff1::new(&key, 10).unwrap();
+        assert!(matches!(ff1.encrypt("12345", b""), Err(NekoError::InvalidInput(_))));
+        assert!(matches!(ff1.encrypt("12345a", b""), Err(NekoError::InvalidInput(_))));
+        assert!(matches!(ff1.encrypt_digits(&[1, 2, 3, 4, 5, 10], b""), Err(NekoError::InvalidInput(_))));
+        assert!(ff1.encrypt("123456", b"").is_ok());
+
+        assert!(matches!(Ff1::new(&key, 1), Err(NekoError::InvalidInput(_))));
+        assert!(matches!(Ff1::new(&key, MAX_RADIX + 1), Err(NekoError::InvalidInput(_))));
+        assert!(matches!(Ff1::new(&key[..10], 10), Err(NekoError::KeyError(_))));
+        assert!(Ff1::with_alphabet(&key, "0123456789012").is_err());
+        assert!(Ff1::with_alphabet(&key, "a").is_err());
+    }
+
+    #[test]
+    fn test_radix_power_bits() {
+        assert_eq!(radix_power_bits(10, 5), 17);
+        assert_eq!(radix_power_bits(2, 8), 8);
+        assert_eq!(radix_power_bits(16, 3), 12);
+        assert_eq!(radix_power_bits(36, 10), 52);
+    }
+}
@@ -0,0 +1,140 @@
+//! HKDF-style key derivation (RFC 5869) over [`NekoMac`]
+//!
+//! Like [`NekoMac`], every function here requires a [`Version::V2`](crate::Version::V2)
+//! hash configuration and returns [`NekoError::InvalidInput`] otherwise.
+
+use crate::{NekoError, NekoHash, NekoMac, NekoResult};
+
+/// Extracts a pseudorandom key from input keying material
+///
+/// An empty salt is replaced by `output_size()` zero bytes, as in RFC 5869.
+pub fn extract<H: NekoHash + Clone>(hash: &H, salt: &[u8], ikm: &[u8]) -> NekoResult<Vec<u8>> {
+    let zero_salt;
+    let salt = if salt.is_empty() {
+        zero_salt = vec![0u8; hash.output_size()];
+        &zero_salt[..]
+    } else {
+        salt
+    };
+    Ok(NekoMac::new(hash.clone(), salt)?.sign(ikm))
+}
+
+/// Expands a pseudorandom key into `len` bytes of output keying material bound to `info`
+pub fn expand<H: NekoHash + Clone>(hash: &H, prk: &[u8], info: &[u8], len: usize) -> NekoResult<Vec<u8>> {
+    let hash_len = hash.output_size();
+    if prk.len() < hash_len {
+        return Err(NekoError::InvalidInput(format!("PRK must be at least {} bytes", hash_len)));
+    }
+    if len > 255 * hash_len {
+        return Err(NekoError::InvalidInput(format!("Output length must be at most {} bytes", 255 * hash_len)));
+    }
+
+    let mac = NekoMac::new(hash.clone(), prk)?;
+    let mut okm = Vec::with_capacity(len);
+    let mut block: Vec<u8> = Vec::new();
+    let mut counter = 1u8;
+
+    while okm.len() < len {
+        let mut input = Vec::with_capacity(block.len() + info.len() + 1);
+        input.extend_from_slice(&block);
+        input.extend_from_slice(info);
+        input.push(counter);
+        block = mac.sign(&input);
+
+        let take = (len - okm.len()).min(block.len());
+        okm.extend_from_slice(&block[..take]);
+        counter = counter.wrapping_add(1);
+    }
+
+    Ok(okm)
+}
+
+/// Runs [`extract`] followed by [`expand`]
+pub fn derive<H: NekoHash + Clone>(
+    hash: &H,
+    salt: &[u8],
+    ikm: &[u8],
+    info: &[u8],
+    len: usize,
+) -> NekoResult<Vec<u8>> {
+    let prk = extract(hash, salt, ikm)?;
+    expand(hash, &prk, info, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{KawaiiHash, MagicalHash, TsundereHash, Version, utils};
+
+    // Inputs of RFC 5869 test case 1
+    const IKM: [u8; 22] = [0x0b; 22];
+    const SALT: [u8; 13] = [0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c];
+    const INFO: [u8; 10] = [0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9];
+
+    #[test]
+    fn test_vectors() {
+        let hash = TsundereHash::with_version(Version::V2);
+        let prk = extract(&hash, &SALT, &IKM).unwrap();
+        assert_eq!(
+            utils::to_hex(&prk),
+            "21f82e8a784cd944c702b186a4f330fde47b235334f274e6de796904782b4461"
+        );
+
+        let okm = expand(&hash, &prk, &INFO, 42).unwrap();
+        assert_eq!(
+            utils::to_hex(&okm),
+            "1af28c1fba59fdcfb06883c1ce0c2bcc01cebd8bd0247cc27baa88f19b3c971fa0744f7da561b582da23"
+        );
+
+        // Empty salt and info, as in RFC 5869 test case 3
+        let okm = derive(&MagicalHash::with_version(Version::V2), &[], &IKM, &[], 42).unwrap();
+        assert_eq!(
+            utils::to_hex(&okm),
+            "6163f82f134ac0e67dda9133d1e7e127a23b022dff2e800b5dfe734dc3bdaa87ae95c552e41e904201e9"
+        );
+    }
+
+    #[test]
+    fn test_labels_separate_keys() {
+        let hash = KawaiiHash::with_version(Version::V2);
+        let prk = extract(&hash, b"salt", b"master secret").unwrap();
+        let enc = expand(&hash, &prk, b"encryption", 32).unwrap();
+        let mac = expand(&hash, &prk, b"authentication", 32).unwrap();
+        assert_ne!(enc, mac);
+
+        // Shorter outputs are prefixes of longer ones
+        let long = expand(&hash, &prk, b"encryption", 100).unwrap();
+        assert_eq!(long[..32], enc[..]);
+    }
+
+    #[test]
+    fn test_invalid_lengths() {
+        let hash = MagicalHash::with_version(Version::V2);
+        let prk = extract(&hash, b"salt", b"ikm").unwrap();
+        assert!(expand(&hash, &prk, b"", 255 * 16).is_ok());
+        assert!(expand(&hash, &prk, b"", 255 * 16 + 1).is_err());
+        assert!(expand(&hash, &prk[..15], b"", 16).is_err());
+        assert!(expand(&hash, &prk, b"", 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rejects_v1_hash() {
+        let hash = TsundereHash::new();
+        assert!(matches!(extract(&hash, &SALT, &IKM), Err(NekoError::InvalidInput(_))));
+        assert!(matches!(expand(&hash, &[0u8; 32], &INFO, 32), Err(NekoError::InvalidInput(_))));
+        assert!(derive(&hash, &SALT, &IKM, &INFO, 32).is_err());
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn test_matches_reference_hkdf() {
+        use crate::TsundereV2Hasher;
+
+        let reference = ::hkdf::SimpleHkdf::<TsundereV2Hasher>::new(Some(&SALT), &IKM);
+        let mut expected = [0u8; 100];
+        reference.expand(&INFO, &mut expected).unwrap();
+
+        let okm = derive(&TsundereHash::with_version(Version::V2), &SALT, &IKM, &INFO, 100).unwrap();
+        assert_eq!(okm, expected);
+    }
+}
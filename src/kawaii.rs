@@ -1,11 +1,18 @@
-use rand::{Rng, SeedableRng, rngs::StdRng};
-use crate::{NekoHash};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Read};
+use std::sync::OnceLock;
+use rand::{Rng, thread_rng};
+use crate::{Algorithm, NekoHash, NekoHasher, Version, prng::NekoRng, utils};
 
 /// KawaiiHash implementation with configurable output size
+#[derive(Clone, Debug)]
 pub struct KawaiiHash {
     size: usize,
     seed: u64,
-    rng: StdRng,
+    version: Version,
+    personalization: Vec<u8>,
+    schedule: NekoRng,
+    key: Option<NekoRng>,
 }
 
 impl Default for KawaiiHash {
@@ -22,21 +29,12 @@ impl KawaiiHash {
 
     /// Creates a new KawaiiHash with specified output size
     pub fn with_size(size: usize) -> Self {
-        let seed = 0xDEADBEEF;
-        Self {
-            size,
-            seed,
-            rng: StdRng::seed_from_u64(seed),
-        }
+        Self::with_size_and_seed(size, 0xDEADBEEF)
     }
 
     /// Creates a new KawaiiHash with specified seed
     pub fn with_seed(seed: u64) -> Self {
-        Self {
-            size: 32,
-            seed,
-            rng: StdRng::seed_from_u64(seed),
-        }
+        Self::with_size_and_seed(32, seed)
     }
 
     /// Creates a new KawaiiHash with specified size and seed
@@ -44,44 +42,246 @@ impl KawaiiHash {
         Self {
             size,
             seed,
-            rng: StdRng::seed_from_u64(seed),
+            version: Version::V1,
+            personalization: Vec::new(),
+            schedule: NekoRng::from_seed(seed),
+            key: None,
+        }
+    }
+
+    /// Creates a new KawaiiHash with default size and seed using the specified algorithm version
+    pub fn with_version(version: Version) -> Self {
+        Self::new().versioned(version)
+    }
+
+    /// Switches this configuration to the specified algorithm version
+    pub fn versioned(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Creates a new KawaiiHash with default size and seed bound to a personalization string
+    ///
+    /// Digests computed under different personalization strings are unrelated,
+    /// which keeps hashes made for different purposes from being confused.
+    pub fn with_personalization(personalization: &[u8]) -> Self {
+        Self::new().personalized(personalization)
+    }
+
+    /// Binds this configuration to a personalization string
+    ///
+    /// An empty string leaves the configuration unpersonalized.
+    pub fn personalized(mut self, personalization: &[u8]) -> Self {
+        self.personalization = personalization.to_vec();
+        self.schedule = self.derive_schedule();
+        self
+    }
+
+    /// Creates a new keyed v2 KawaiiHash with default size
+    pub fn with_key(key: &[u8; 32]) -> Self {
+        Self::new().versioned(Version::V2).keyed(key)
+    }
+
+    /// Keys this configuration with a 256-bit secret
+    ///
+    /// The key is used directly as the constant schedule's ChaCha key,
+    /// replacing the seed, and the state starts from bytes drawn from that
+    /// schedule instead of zeros, so the full key space protects the output.
+    ///
+    /// Under [`Version::V1`] the key does not fix the structural collisions
+    /// of the v1 absorption, e.g. `a` and `a\0` still collide, so keyed
+    /// configurations should use [`Version::V2`] as [`KawaiiHash::with_key`] does.
+    pub fn keyed(mut self, key: &[u8; 32]) -> Self {
+        self.key = Some(NekoRng::from_key(key));
+        self.schedule = self.derive_schedule();
+        self
+    }
+
+    /// Hashes the input data into a fixed-size array without allocating
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` differs from the configured output size.
+    pub fn hash_array<const N: usize>(&self, data: &[u8]) -> [u8; N] {
+        let mut out = [0u8; N];
+        self.hash_into(data, &mut out);
+        out
+    }
+
+    /// Hashes the input data into an extendable output stream
+    ///
+    /// Any number of bytes can be drawn from the returned reader, and shorter
+    /// reads are always a prefix of longer ones.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the configured output size is below 32 bytes.
+    pub fn xof(&self, data: &[u8]) -> KawaiiXof {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finalize_xof()
+    }
+
+    /// Derives the constant schedule from the key or seed and personalization
+    fn derive_schedule(&self) -> NekoRng {
+        let rng = match &self.key {
+            Some(rng) => rng.clone(),
+            None => NekoRng::from_seed(self.seed),
+        };
+        if self.personalization.is_empty() {
+            rng
+        } else {
+            rng.personalize(&self.personalization)
         }
     }
 }
 
-impl NekoHash for KawaiiHash {
-    fn hash(&self, data: &[u8]) -> Vec<u8> {
-        let mut result = vec![0u8; self.size];
-        let mut rng = StdRng::seed_from_u64(self.seed);
+/// Sets the starting state: zeros, or secret bytes from the schedule when keyed
+fn init_state(state: &mut [u8], rng: &mut NekoRng, keyed: bool) {
+    if keyed {
+        rng.fill_bytes(state);
+    } else {
+        state.fill(0);
+    }
+}
 
-        // Initialize result with input data
-        for (i, &byte) in data.iter().enumerate() {
-            result[i % self.size] ^= byte;
+/// Folds input data into the state, continuing after `offset` absorbed bytes
+fn absorb(state: &mut [u8], offset: u64, data: &[u8]) {
+    let size = state.len() as u64;
+    for (i, &byte) in data.iter().enumerate() {
+        state[((offset + i as u64) % size) as usize] ^= byte;
+    }
+}
+
+/// Folds input data into the state like [`absorb`], mixing the state
+/// with fresh constants every time a block of `state.len()` bytes completes
+fn absorb_v2(state: &mut [u8], rng: &mut NekoRng, offset: u64, data: &[u8]) {
+    let size = state.len() as u64;
+    for (i, &byte) in data.iter().enumerate() {
+        let pos = ((offset + i as u64) % size) as usize;
+        state[pos] ^= byte;
+        if pos == state.len() - 1 {
+            mix(state, rng);
         }
+    }
+}
 
-        // Apply kawaii transformations
-        for i in 0..self.size {
-            let random = rng.gen::<u8>();
-            result[i] = result[i].wrapping_add(random);
-            result[i] = result[i].rotate_left(3);
-            
-            if i > 0 {
-                result[i] ^= result[i - 1];
-            }
+/// Appends the v2 padding (`0x80` followed by the little-endian message
+/// length) to a state that has absorbed `len` bytes
+fn pad_v2(state: &mut [u8], rng: &mut NekoRng, len: u64) {
+    absorb_v2(state, rng, len, &[0x80]);
+    absorb_v2(state, rng, len + 1, &len.to_le_bytes());
+}
+
+/// Applies the kawaii transformations and final mixing to the state in place
+fn mix(result: &mut [u8], rng: &mut NekoRng) {
+    let size = result.len();
+
+    // Apply kawaii transformations
+    for i in 0..size {
+        let random = rng.next_u8();
+        result[i] = result[i].wrapping_add(random);
+        result[i] = result[i].rotate_left(3);
+        
+        if i > 0 {
+            result[i] ^= result[i - 1];
         }
+    }
+
+    // Final mixing
+    for i in (0..size).rev() {
+        let random = rng.next_u8();
+        result[i] = result[i].wrapping_mul(0xB5);
+        result[i] ^= random;
+        
+        if i < size - 1 {
+            result[i] ^= result[i + 1];
+        }
+    }
+}
+
+impl NekoHash for KawaiiHash {
+    type Hasher = KawaiiHasher;
+
+    fn hasher(&self) -> KawaiiHasher {
+        let mut hasher = KawaiiHasher {
+            size: self.size,
+            version: self.version,
+            keyed: self.key.is_some(),
+            state: vec![0u8; self.size],
+            len: 0,
+            rng: self.schedule.clone(),
+            schedule: self.schedule.clone(),
+        };
+        hasher.reset();
+        hasher
+    }
+
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::Kawaii
+    }
+
+    fn version(&self) -> Version {
+        self.version
+    }
 
-        // Final mixing
-        for i in (0..self.size).rev() {
-            let random = rng.gen::<u8>();
-            result[i] = result[i].wrapping_mul(0xB5);
-            result[i] ^= random;
-            
-            if i < self.size - 1 {
-                result[i] ^= result[i + 1];
+    fn hash_into(&self, data: &[u8], out: &mut [u8]) {
+        assert_eq!(out.len(), self.size, "output buffer must match the hash output size");
+        let mut rng = self.schedule.clone();
+        init_state(out, &mut rng, self.key.is_some());
+        match self.version {
+            Version::V1 => absorb(out, 0, data),
+            Version::V2 => {
+                absorb_v2(out, &mut rng, 0, data);
+                pad_v2(out, &mut rng, data.len() as u64);
             }
         }
+        mix(out, &mut rng);
+    }
+
+    fn output_size(&self) -> usize {
+        self.size
+    }
+}
+
+/// Incremental KawaiiHash state, created by [`KawaiiHash::hasher`]
+#[derive(Clone, Debug)]
+pub struct KawaiiHasher {
+    size: usize,
+    version: Version,
+    keyed: bool,
+    state: Vec<u8>,
+    len: u64,
+    rng: NekoRng,
+    schedule: NekoRng,
+}
+
+impl Default for KawaiiHasher {
+    fn default() -> Self {
+        KawaiiHash::new().hasher()
+    }
+}
+
+impl NekoHasher for KawaiiHasher {
+    fn update(&mut self, data: &[u8]) {
+        match self.version {
+            Version::V1 => absorb(&mut self.state, self.len, data),
+            Version::V2 => absorb_v2(&mut self.state, &mut self.rng, self.len, data),
+        }
+        self.len += data.len() as u64;
+    }
+
+    fn finalize(mut self) -> Vec<u8> {
+        if self.version == Version::V2 {
+            pad_v2(&mut self.state, &mut self.rng, self.len);
+        }
+        mix(&mut self.state, &mut self.rng);
+        self.state
+    }
 
-        result
+    fn finalize_into(self, out: &mut [u8]) {
+        assert_eq!(out.len(), self.size, "output buffer must match the hash output size");
+        out.copy_from_slice(&self.finalize());
     }
 
     fn output_size(&self) -> usize {
@@ -89,7 +289,120 @@ impl NekoHash for KawaiiHash {
     }
 
     fn reset(&mut self) {
-        self.rng = StdRng::seed_from_u64(self.seed);
+        self.len = 0;
+        self.rng = self.schedule.clone();
+        init_state(&mut self.state, &mut self.rng, self.keyed);
+    }
+}
+
+impl KawaiiHasher {
+    /// Consumes the state and produces an extendable output stream
+    ///
+    /// The stream is keyed by the v2 KawaiiHash of the digest this state
+    /// would finalize to, so its strength is bounded by the configured size.
+    /// Sizes below 32 bytes are rejected rather than yielding a weak stream.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the configured output size is below 32 bytes.
+    pub fn finalize_xof(self) -> KawaiiXof {
+        assert!(self.size >= 32, "extendable output requires an output size of at least 32 bytes");
+        let digest = self.finalize();
+
+        let mut hasher = KawaiiHash::with_version(Version::V2).hasher();
+        hasher.update(b"nekohash.xof");
+        hasher.update(&digest);
+
+        let mut key = [0u8; 32];
+        hasher.finalize_into(&mut key);
+        KawaiiXof {
+            rng: NekoRng::from_key(&key),
+            word: [0; 4],
+            used: 4,
+        }
+    }
+}
+
+/// Extendable output reader, created by [`KawaiiHash::xof`] or [`KawaiiHasher::finalize_xof`]
+#[derive(Clone, Debug)]
+pub struct KawaiiXof {
+    rng: NekoRng,
+    word: [u8; 4],
+    used: usize,
+}
+
+impl KawaiiXof {
+    /// Fills `out` with the next bytes of the output stream
+    pub fn squeeze(&mut self, out: &mut [u8]) {
+        for byte in out {
+            if self.used == self.word.len() {
+                self.word = self.rng.next_u32().to_le_bytes();
+                self.used = 0;
+            }
+            *byte = self.word[self.used];
+            self.used += 1;
+        }
+    }
+
+    /// Returns the next `len` bytes of the output stream
+    pub fn squeeze_vec(&mut self, len: usize) -> Vec<u8> {
+        let mut out = vec![0u8; len];
+        self.squeeze(&mut out);
+        out
+    }
+}
+
+impl Read for KawaiiXof {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.squeeze(buf);
+        Ok(buf.len())
+    }
+}
+
+impl Hasher for KawaiiHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        utils::digest_to_u64(&self.clone().finalize())
+    }
+}
+
+/// [`BuildHasher`] producing seeded v2 [`KawaiiHasher`]s for `HashMap`/`HashSet`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KawaiiBuildHasher {
+    seed: u64,
+}
+
+impl Default for KawaiiBuildHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KawaiiBuildHasher {
+    /// Creates a builder using a random seed chosen once per process
+    ///
+    /// Hashers use v2 absorption, so colliding keys cannot be crafted by
+    /// rearranging or zero-padding input; the seed keeps the remaining
+    /// bucket layout unpredictable from outside.
+    pub fn new() -> Self {
+        static PROCESS_SEED: OnceLock<u64> = OnceLock::new();
+        Self::with_seed(*PROCESS_SEED.get_or_init(|| thread_rng().gen()))
+    }
+
+    /// Creates a builder with a fixed seed for reproducible hashing
+    pub fn with_seed(seed: u64) -> Self {
+        Self { seed }
+    }
+}
+
+impl BuildHasher for KawaiiBuildHasher {
+    type Hasher = KawaiiHasher;
+
+    fn build_hasher(&self) -> KawaiiHasher {
+        KawaiiHash::with_seed(self.seed).versioned(Version::V2).hasher()
     }
 }
 
@@ -124,4 +437,219 @@ mod tests {
         
         assert_eq!(hash1, hash2);
     }
+
+    #[test]
+    fn test_kawaii_streaming_matches_oneshot() {
+        let hasher = KawaiiHash::with_size(16);
+        let input: Vec<u8> = (0..100u8).collect();
+        let expected = hasher.hash(&input);
+
+        for split in [0, 1, 15, 16, 17, 99, 100] {
+            let mut state = hasher.hasher();
+            state.update(&input[..split]);
+            state.update(&input[split..]);
+            assert_eq!(state.finalize(), expected);
+        }
+    }
+
+    #[test]
+    fn test_kawaii_hash_array() {
+        let hasher = KawaiiHash::with_size(16);
+        let input = b"Hello, World!";
+        let array: [u8; 16] = hasher.hash_array(input);
+        assert_eq!(array.to_vec(), hasher.hash(input));
+
+        let mut out = [0xFFu8; 16];
+        hasher.hash_into(input, &mut out);
+        assert_eq!(out, array);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_kawaii_hash_into_wrong_size() {
+        KawaiiHash::new().hash_into(b"Hello, World!", &mut [0u8; 16]);
+    }
+
+    #[test]
+    fn test_kawaii_build_hasher() {
+        use std::collections::HashMap;
+
+        let build = KawaiiBuildHasher::with_seed(12345);
+        assert_eq!(build.hash_one("neko"), build.hash_one("neko"));
+        assert_ne!(build.hash_one("neko"), KawaiiBuildHasher::with_seed(54321).hash_one("neko"));
+        assert_eq!(KawaiiBuildHasher::new(), KawaiiBuildHasher::default());
+
+        let mut map = HashMap::with_hasher(build);
+        map.insert("kawaii", 1);
+        map.insert("tsundere", 2);
+        assert_eq!(map.get("kawaii"), Some(&1));
+        assert_eq!(map.get("tsundere"), Some(&2));
+
+        // Block-swapped keys collide under v1 absorption
+        let x = "x".repeat(31);
+        assert_ne!(build.hash_one(format!("A{x}B")), build.hash_one(format!("B{x}A")));
+    }
+
+    #[test]
+    fn test_kawaii_hasher_reset() {
+        let hasher = KawaiiHash::new();
+        let mut state = hasher.hasher();
+        state.update(b"garbage");
+        state.reset();
+        state.update(b"Hello, World!");
+        assert_eq!(state.finalize(), hasher.hash(b"Hello, World!"));
+    }
+
+    #[test]
+    fn test_kawaii_golden_vectors() {
+        // Pinned outputs: any change here breaks every stored digest
+        assert_eq!(
+            utils::to_hex(&KawaiiHash::new().hash(b"")),
+            "5cdd22f8492a10cec68addea8f53f1d67cb20732bbbf01a979f58258a57552ca"
+        );
+        assert_eq!(
+            utils::to_hex(&KawaiiHash::new().hash(b"Hello, World!")),
+            "adcffc3b4b8eed65dc3dcc2a9fce71db3ceff7771b2ae16431709aed65e8fa4f"
+        );
+        assert_eq!(
+            utils::to_hex(&KawaiiHash::with_size_and_seed(16, 7).hash(b"Hello, World!")),
+            "4895d6e7ed94d23d57fd8faea8108564"
+        );
+        assert_eq!(
+            utils::to_hex(&KawaiiHash::with_version(Version::V2).hash(b"Hello, World!")),
+            "e183b07707c2a12990718066d382e17c3c587728dbb5a1e3a9df3272256f2250"
+        );
+    }
+
+    #[test]
+    fn test_kawaii_v2_absorption() {
+        let v1 = KawaiiHash::with_size(16);
+        let v2 = KawaiiHash::with_size(16).versioned(Version::V2);
+
+        // V1 cannot see trailing zeros, V2 can
+        assert_eq!(v1.hash(b"a"), v1.hash(b"a\0"));
+        assert_ne!(v2.hash(b"a"), v2.hash(b"a\0"));
+        assert_ne!(v2.hash(b""), v2.hash(b"\0"));
+
+        // Swapping whole blocks must change the digest
+        let a = [0x11u8; 16];
+        let b = [0x22u8; 16];
+        assert_ne!(v2.hash(&[a, b].concat()), v2.hash(&[b, a].concat()));
+
+        assert_ne!(v1.hash(b"Hello, World!"), v2.hash(b"Hello, World!"));
+    }
+
+    #[test]
+    fn test_kawaii_v2_streaming_matches_oneshot() {
+        let hasher = KawaiiHash::with_size(16).versioned(Version::V2);
+        let input: Vec<u8> = (0..100u8).collect();
+        let expected = hasher.hash(&input);
+
+        for split in [0, 1, 15, 16, 17, 100] {
+            let mut state = hasher.hasher();
+            state.update(&input[..split]);
+            state.update(&input[split..]);
+            assert_eq!(state.finalize(), expected);
+        }
+
+        let mut state = hasher.hasher();
+        state.update(&input);
+        state.reset();
+        state.update(&input);
+        assert_eq!(state.finalize(), expected);
+    }
+
+    #[test]
+    fn test_kawaii_personalization() {
+        let input = b"Hello, World!";
+        let cache = KawaiiHash::with_personalization(b"myapp.cache.v1");
+        let files = KawaiiHash::with_personalization(b"myapp.files.v1");
+
+        assert_eq!(cache.hash(input), KawaiiHash::with_personalization(b"myapp.cache.v1").hash(input));
+        assert_ne!(cache.hash(input), files.hash(input));
+        assert_ne!(cache.hash(input), KawaiiHash::new().hash(input));
+        assert_eq!(KawaiiHash::with_personalization(b"").hash(input), KawaiiHash::new().hash(input));
+
+        let v2 = KawaiiHash::with_version(Version::V2).personalized(b"myapp.cache.v1");
+        let mut state = v2.hasher();
+        state.update(&input[..4]);
+        state.update(&input[4..]);
+        assert_eq!(state.finalize(), v2.hash(input));
+    }
+
+    #[test]
+    fn test_kawaii_xof_prefix_consistent() {
+        let hasher = KawaiiHash::new();
+        let input = b"Hello, World!";
+        let long = hasher.xof(input).squeeze_vec(200);
+
+        let mut xof = hasher.xof(input);
+        let mut pieces = Vec::new();
+        for len in [1, 3, 4, 7, 32, 153] {
+            pieces.extend(xof.squeeze_vec(len));
+        }
+        assert_eq!(pieces, long);
+        assert_eq!(hasher.xof(input).squeeze_vec(32), long[..32]);
+
+        let mut read = [0u8; 64];
+        hasher.xof(input).read_exact(&mut read).unwrap();
+        assert_eq!(read, long[..64]);
+
+        let mut state = hasher.hasher();
+        state.update(&input[..5]);
+        state.update(&input[5..]);
+        assert_eq!(state.finalize_xof().squeeze_vec(200), long);
+
+        assert_ne!(hasher.xof(b"Hello, World?").squeeze_vec(32), long[..32]);
+        assert_ne!(long[..32], hasher.hash(input)[..]);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 32 bytes")]
+    fn test_kawaii_xof_rejects_small_size() {
+        KawaiiHash::with_size(16).xof(b"Hello, World!");
+    }
+
+    #[test]
+    fn test_kawaii_keyed() {
+        let input = b"Hello, World!";
+        let key: [u8; 32] = std::array::from_fn(|i| 0xA0 + i as u8);
+        let mut other = key;
+        other[31] ^= 1;
+
+        let keyed = KawaiiHash::with_key(&key);
+        assert_eq!(keyed.hash(input), KawaiiHash::with_key(&key).hash(input));
+        assert_ne!(keyed.hash(input), KawaiiHash::with_key(&other).hash(input));
+        assert_ne!(keyed.hash(input), KawaiiHash::with_version(Version::V2).hash(input));
+        assert_ne!(keyed.hash(input), keyed.clone().personalized(b"context").hash(input));
+        assert_eq!(
+            KawaiiHash::new().keyed(&key).personalized(b"context").hash(input),
+            KawaiiHash::new().personalized(b"context").keyed(&key).hash(input)
+        );
+        assert_ne!(keyed.hash(b"a"), keyed.hash(b"a\0"));
+
+        // Neither the key bytes nor its schedule words may appear in Debug output
+        let debug = format!("{:?}", keyed);
+        assert!(!debug.contains(&format!("{:?}", key)));
+        assert!(!debug.contains(&utils::to_hex(&key)));
+        assert!(!debug.contains(&u32::from_le_bytes([0xA0, 0xA1, 0xA2, 0xA3]).to_string()));
+    }
+
+    #[test]
+    fn test_kawaii_keyed_custom_size_streaming() {
+        // The keyed starting state must cover the whole configured size
+        let key = [0x42u8; 32];
+        let hasher = KawaiiHash::with_size(64).versioned(Version::V2).keyed(&key);
+        let input: Vec<u8> = (0..150u8).collect();
+        let expected = hasher.hash(&input);
+        assert_eq!(expected.len(), 64);
+        assert_eq!(hasher.hash_array::<64>(&input).to_vec(), expected);
+
+        let mut state = hasher.hasher();
+        state.update(&input[..70]);
+        state.reset();
+        state.update(&input[..70]);
+        state.update(&input[70..]);
+        assert_eq!(state.finalize(), expected);
+    }
 }
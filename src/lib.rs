@@ -1,11 +1,30 @@
 use std::error::Error;
 use std::fmt;
 
+pub mod blind_index;
+pub mod envelope;
+pub mod fpe;
+pub mod hkdf;
 pub mod kawaii;
+pub mod mac;
 pub mod magical;
+pub mod otp;
+pub mod output;
+pub mod password;
+mod prng;
+pub mod siv;
+pub mod stream;
 pub mod tsundere;
 pub mod utils;
 
+#[cfg(feature = "digest")]
+mod digest_impl;
+
+#[cfg(feature = "digest")]
+pub use digest;
+#[cfg(feature = "digest")]
+pub use digest_impl::{Kawaii256, Kawaii512, MagicalV2Hasher, SizedKawaiiHasher, TsundereV2Hasher};
+
 /// Custom error type for the Nekohash library
 #[derive(Debug)]
 pub enum NekoError {
@@ -54,29 +73,136 @@ impl From<std::io::Error> for NekoError {
 /// Result type for Nekohash operations
 pub type NekoResult<T> = Result<T, NekoError>;
 
+/// Revision of the hash algorithms
+///
+/// `V1` folds input bytes into the state position-blindly, so e.g. trailing
+/// zero bytes do not change the digest. `V2` mixes the state after every
+/// block and appends padding and the message length before finalizing.
+/// `V1` remains the default so existing digests stay reproducible.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Version {
+    /// Original absorption
+    #[default]
+    V1,
+    /// Length- and position-aware absorption
+    V2,
+}
+
 /// Trait for hash implementations
 pub trait NekoHash {
+    /// Incremental state used to hash data supplied in chunks
+    type Hasher: NekoHasher;
+
+    /// Creates a fresh incremental state for this hash configuration
+    fn hasher(&self) -> Self::Hasher;
+
+    /// Get the algorithm implemented by this hash
+    fn algorithm(&self) -> Algorithm;
+
+    /// Get the absorption version used by this hash
+    fn version(&self) -> Version;
+
     /// Hash the input data
-    fn hash(&self, data: &[u8]) -> Vec<u8>;
+    fn hash(&self, data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0u8; self.output_size()];
+        self.hash_into(data, &mut out);
+        out
+    }
+
+    /// Hash the input data into a caller-provided buffer
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out.len()` differs from [`NekoHash::output_size`].
+    fn hash_into(&self, data: &[u8], out: &mut [u8]) {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finalize_into(out);
+    }
+
+    /// Hash the input data into a digest tagged with its algorithm
+    fn digest(&self, data: &[u8]) -> NekoDigest {
+        NekoDigest::new(self.algorithm(), self.hash(data))
+    }
+
+    /// Check in constant time whether the data hashes to the expected digest
+    fn verify(&self, data: &[u8], expected: &NekoDigest) -> bool {
+        self.digest(data) == *expected
+    }
     
     /// Hash the input data with encryption
-    fn hash_encrypted(&self, data: &[u8], key: Option<&[u8]>) -> NekoResult<Vec<u8>> {
-        let hash = self.hash(data);
-        utils::encrypt_data(&hash, key)
+    ///
+    /// Uses `key` if given, otherwise a freshly generated key; either way the
+    /// key is returned alongside the ciphertext.
+    fn hash_encrypted(&self, data: &[u8], key: Option<&[u8]>) -> NekoResult<EncryptedHash> {
+        let key = match key {
+            Some(key) => key.to_vec(),
+            None => utils::generate_key(),
+        };
+        let ciphertext = utils::encrypt_data(&self.hash(data), Some(&key))?;
+        Ok(EncryptedHash::new(ciphertext, key))
+    }
+
+    /// Decrypt an encrypted hash with `key` and compare it to the hash of `data` in constant time
+    fn verify_encrypted(&self, data: &[u8], encrypted: &EncryptedHash, key: &[u8]) -> NekoResult<bool> {
+        let decrypted = utils::decrypt_data(encrypted.ciphertext(), key)?;
+        Ok(utils::constant_time_compare(&self.hash(data), &decrypted))
     }
     
     /// Get the output size of the hash in bytes
     fn output_size(&self) -> usize;
+
+    /// Get the block size in bytes used when padding keys for [`mac::NekoMac`]
+    fn block_size(&self) -> usize {
+        64
+    }
     
     /// Reset the hash state if applicable
+    #[deprecated(since = "0.3.0", note = "hash configurations hold no state; use `NekoHasher::reset`")]
     fn reset(&mut self) {
         // Default implementation does nothing
     }
 }
 
-pub use kawaii::KawaiiHash;
-pub use magical::MagicalHash;
-pub use tsundere::TsundereHash;
+/// Trait for incremental hash states
+///
+/// Feeding the input through any sequence of `update` calls produces the
+/// same digest as a single call to [`NekoHash::hash`].
+pub trait NekoHasher {
+    /// Absorb the next chunk of input
+    fn update(&mut self, data: &[u8]);
+
+    /// Consume the state and produce the digest
+    fn finalize(self) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        let mut out = vec![0u8; self.output_size()];
+        self.finalize_into(&mut out);
+        out
+    }
+
+    /// Consume the state and write the digest into a caller-provided buffer
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out.len()` differs from [`NekoHasher::output_size`].
+    fn finalize_into(self, out: &mut [u8])
+    where
+        Self: Sized;
+
+    /// Get the output size of the hash in bytes
+    fn output_size(&self) -> usize;
+
+    /// Reset the state as if no data had been absorbed
+    fn reset(&mut self);
+}
+
+pub use mac::NekoMac;
+pub use output::{Algorithm, EncryptedHash, NekoDigest};
+pub use kawaii::{KawaiiBuildHasher, KawaiiHash, KawaiiHasher, KawaiiXof};
+pub use magical::{MagicalHash, MagicalHasher};
+pub use tsundere::{TsundereHash, TsundereHasher};
 
 #[cfg(test)]
 mod tests {
@@ -91,14 +217,23 @@ mod tests {
         // Test with provided key
         let key = [42u8; 32];
         let encrypted = hasher.hash_encrypted(data, Some(&key)).unwrap();
-        let decrypted = utils::decrypt_data(&encrypted, &key).unwrap();
+        assert_eq!(encrypted.key(), &key);
+        let decrypted = utils::decrypt_data(encrypted.ciphertext(), &key).unwrap();
         assert_eq!(hash, decrypted);
+        assert!(hasher.verify_encrypted(data, &encrypted, &key).unwrap());
+        assert!(!hasher.verify_encrypted(b"Hello, World?", &encrypted, &key).unwrap());
 
-        // Test with random key (should fail with different key)
+        // Test with random key (returned so the hash stays recoverable)
         let encrypted_random = hasher.hash_encrypted(data, None).unwrap();
+        assert_eq!(encrypted_random.key().len(), 32);
+        assert_eq!(encrypted_random.decrypt().unwrap(), hash);
+        assert!(hasher.verify_encrypted(data, &encrypted_random, encrypted_random.key()).unwrap());
+
         let wrong_key = [7u8; 32];
-        let result = utils::decrypt_data(&encrypted_random, &wrong_key);
-        assert!(result.is_err() || result.unwrap() != hash);
+        let result = hasher.verify_encrypted(data, &encrypted_random, &wrong_key);
+        assert!(result.is_err() || !result.unwrap());
+        assert!(hasher.hash_encrypted(data, Some(&[1u8; 16])).is_err());
+        assert!(!format!("{:?}", encrypted_random).contains(&format!("{:?}", encrypted_random.key())));
     }
 }
 
@@ -0,0 +1,194 @@
+use crate::{NekoError, NekoHash, NekoHasher, NekoResult, Version, utils};
+
+const INNER_PAD: u8 = 0x36;
+const OUTER_PAD: u8 = 0x5c;
+
+/// HMAC construction over any [`NekoHash`]
+///
+/// Computes `H((K ^ opad) || H((K ^ ipad) || message))`, where `K` is the key
+/// zero-padded to [`NekoHash::block_size`] (or first hashed if it is longer).
+///
+/// The hash must use [`Version::V2`]: the v1 absorption lets `m` and
+/// `m || 0x00` collide, which would carry over to the tags.
+pub struct NekoMac<H: NekoHash> {
+    hash: H,
+    inner_pad: Vec<u8>,
+    outer_pad: Vec<u8>,
+    inner: H::Hasher,
+}
+
+impl<H: NekoHash> NekoMac<H> {
+    /// Creates a MAC keyed with `key` using the given hash configuration
+    ///
+    /// Returns [`NekoError::InvalidInput`] if `hash` uses [`Version::V1`].
+    pub fn new(hash: H, key: &[u8]) -> NekoResult<Self> {
+        if hash.version() != Version::V2 {
+            return Err(NekoError::InvalidInput("NekoMac requires a v2 hash configuration".into()));
+        }
+
+        let block_size = hash.block_size();
+        let mut key_block = if key.len() > block_size {
+            hash.hash(key)
+        } else {
+            key.to_vec()
+        };
+        key_block.resize(block_size, 0);
+
+        let inner_pad: Vec<u8> = key_block.iter().map(|b| b ^ INNER_PAD).collect();
+        let outer_pad: Vec<u8> = key_block.iter().map(|b| b ^ OUTER_PAD).collect();
+
+        let mut inner = hash.hasher();
+        inner.update(&inner_pad);
+
+        Ok(Self {
+            hash,
+            inner_pad,
+            outer_pad,
+            inner,
+        })
+    }
+
+    /// Computes the tag of a complete message
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        let mut inner = self.hash.hasher();
+        inner.update(&self.inner_pad);
+        inner.update(message);
+        Self::outer(&self.hash, &self.outer_pad, inner)
+    }
+
+    /// Checks in constant time whether `tag` authenticates `message`
+    pub fn verify(&self, message: &[u8], tag: &[u8]) -> bool {
+        utils::constant_time_compare(&self.sign(message), tag)
+    }
+
+    /// Absorbs the next chunk of a streamed message
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// Consumes the MAC and produces the tag of the streamed message
+    pub fn finalize(self) -> Vec<u8> {
+        Self::outer(&self.hash, &self.outer_pad, self.inner)
+    }
+
+    /// Consumes the MAC and checks in constant time whether `tag`
+    /// authenticates the streamed message
+    pub fn finalize_verify(self, tag: &[u8]) -> bool {
+        utils::constant_time_compare(&self.finalize(), tag)
+    }
+
+    /// Discards any streamed data, keeping the key
+    pub fn reset(&mut self) {
+        self.inner = self.hash.hasher();
+        self.inner.update(&self.inner_pad);
+    }
+
+    /// Get the tag size in bytes
+    pub fn output_size(&self) -> usize {
+        self.hash.output_size()
+    }
+
+    fn outer(hash: &H, outer_pad: &[u8], inner: H::Hasher) -> Vec<u8> {
+        let mut outer = hash.hasher();
+        outer.update(outer_pad);
+        outer.update(&inner.finalize());
+        outer.finalize()
+    }
+}
+
+impl<H> Clone for NekoMac<H>
+where
+    H: NekoHash + Clone,
+    H::Hasher: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            hash: self.hash.clone(),
+            inner_pad: self.inner_pad.clone(),
+            outer_pad: self.outer_pad.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{KawaiiHash, MagicalHash, TsundereHash};
+
+    #[test]
+    fn test_sign_and_verify() {
+        let mac = NekoMac::new(TsundereHash::with_version(Version::V2), b"secret key").unwrap();
+        let tag = mac.sign(b"payload");
+        assert_eq!(tag.len(), 32);
+        assert!(mac.verify(b"payload", &tag));
+        assert!(!mac.verify(b"payload!", &tag));
+        assert!(!mac.verify(b"payload", &tag[..31]));
+
+        let other = NekoMac::new(TsundereHash::with_version(Version::V2), b"other key").unwrap();
+        assert!(!other.verify(b"payload", &tag));
+    }
+
+    #[test]
+    fn test_rejects_v1_hash() {
+        assert!(matches!(
+            NekoMac::new(TsundereHash::new(), b"secret key"),
+            Err(NekoError::InvalidInput(_))
+        ));
+        assert!(NekoMac::new(KawaiiHash::new(), b"secret key").is_err());
+        assert!(NekoMac::new(MagicalHash::new(), b"secret key").is_err());
+    }
+
+    #[test]
+    fn test_structural_forgeries_rejected() {
+        let mac = NekoMac::new(KawaiiHash::with_version(Version::V2), b"secret key").unwrap();
+        let tag = mac.sign(b"transfer 10");
+
+        // Neither a trailing zero nor reordered bytes may reuse the tag
+        assert!(!mac.verify(b"transfer 10\0", &tag));
+        assert!(!mac.verify(b"transfer 01", &tag));
+    }
+
+    #[test]
+    fn test_streaming_matches_sign() {
+        let mac = NekoMac::new(MagicalHash::with_version(Version::V2), b"secret key").unwrap();
+        let expected = mac.sign(b"Hello, World!");
+
+        let mut streaming = mac.clone();
+        streaming.update(b"Hello, ");
+        streaming.update(b"World!");
+        assert!(streaming.clone().finalize_verify(&expected));
+        assert_eq!(streaming.finalize(), expected);
+
+        let mut streaming = mac;
+        streaming.update(b"garbage");
+        streaming.reset();
+        streaming.update(b"Hello, World!");
+        assert_eq!(streaming.finalize(), expected);
+    }
+
+    #[test]
+    fn test_long_key_is_hashed() {
+        let hash = KawaiiHash::with_version(Version::V2);
+        let long_key = [0xAAu8; 100];
+        let mac = NekoMac::new(hash.clone(), &long_key).unwrap();
+        let hashed = NekoMac::new(hash.clone(), &hash.hash(&long_key)).unwrap();
+        assert_eq!(mac.sign(b"data"), hashed.sign(b"data"));
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn test_matches_reference_hmac() {
+        use hmac::{Mac, SimpleHmac};
+        use crate::TsundereV2Hasher;
+
+        for key in [&b"key"[..], &[0x0b; 64], &[0xAA; 131]] {
+            let mut reference = SimpleHmac::<TsundereV2Hasher>::new_from_slice(key).unwrap();
+            reference.update(b"The quick brown fox jumps over the lazy dog");
+            let expected = reference.finalize().into_bytes().to_vec();
+
+            let mac = NekoMac::new(TsundereHash::with_version(Version::V2), key).unwrap();
+            assert_eq!(mac.sign(b"The quick brown fox jumps over the lazy dog"), expected);
+        }
+    }
+}
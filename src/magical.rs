@@ -1,12 +1,16 @@
-use rand::{Rng, SeedableRng, rngs::StdRng};
-use crate::NekoHash;
+use std::hash::Hasher;
+use crate::{Algorithm, NekoHash, NekoHasher, Version, prng::NekoRng, utils};
 
 const MAGIC_CONSTANT: u32 = 0x19_95_08_16;
 
 /// MagicalHash implementation with fixed 16-byte output
+#[derive(Clone, Debug)]
 pub struct MagicalHash {
     magic: u32,
-    rng: StdRng,
+    version: Version,
+    personalization: Vec<u8>,
+    schedule: NekoRng,
+    key: Option<NekoRng>,
 }
 
 impl Default for MagicalHash {
@@ -25,57 +29,198 @@ impl MagicalHash {
     pub fn with_magic(magic: u32) -> Self {
         Self {
             magic,
-            rng: StdRng::seed_from_u64(magic as u64),
+            version: Version::V1,
+            personalization: Vec::new(),
+            schedule: NekoRng::from_seed(magic as u64),
+            key: None,
+        }
+    }
+
+    /// Creates a new MagicalHash with default magic number using the specified algorithm version
+    pub fn with_version(version: Version) -> Self {
+        Self::new().versioned(version)
+    }
+
+    /// Switches this configuration to the specified algorithm version
+    pub fn versioned(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Creates a new MagicalHash with default magic number bound to a personalization string
+    pub fn with_personalization(personalization: &[u8]) -> Self {
+        Self::new().personalized(personalization)
+    }
+
+    /// Binds this configuration to a personalization string
+    ///
+    /// An empty string leaves the configuration unpersonalized.
+    pub fn personalized(mut self, personalization: &[u8]) -> Self {
+        self.personalization = personalization.to_vec();
+        self.schedule = self.derive_schedule();
+        self
+    }
+
+    /// Creates a new keyed v2 MagicalHash with default magic number
+    pub fn with_key(key: &[u8; 32]) -> Self {
+        Self::new().versioned(Version::V2).keyed(key)
+    }
+
+    /// Keys this configuration with a 256-bit secret
+    ///
+    /// The key replaces the constant schedule derived from the magic number,
+    /// and the magic lanes of the starting state are masked with bytes drawn
+    /// from that schedule; the magic number still drives the lane transformation.
+    ///
+    /// Under [`Version::V1`] the key does not fix the structural collisions
+    /// of the v1 absorption, e.g. `a` and `a\0` still collide, so keyed
+    /// configurations should use [`Version::V2`] as [`MagicalHash::with_key`] does.
+    pub fn keyed(mut self, key: &[u8; 32]) -> Self {
+        self.key = Some(NekoRng::from_key(key));
+        self.schedule = self.derive_schedule();
+        self
+    }
+
+    /// Hashes the input data into a 16-byte array without allocating
+    pub fn hash_array(&self, data: &[u8]) -> [u8; 16] {
+        let mut out = [0u8; 16];
+        self.hash_into(data, &mut out);
+        out
+    }
+
+    /// Derives the constant schedule from the key or seed and personalization
+    fn derive_schedule(&self) -> NekoRng {
+        let rng = match &self.key {
+            Some(rng) => rng.clone(),
+            None => NekoRng::from_seed(self.magic as u64),
+        };
+        if self.personalization.is_empty() {
+            rng
+        } else {
+            rng.personalize(&self.personalization)
         }
     }
 }
 
+/// Applies the magical transformation to each 32-bit lane of the state
+fn transform(state: &mut [u8; 16], magic: u32) {
+    for chunk in state.chunks_mut(4) {
+        let mut value = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+
+        value = value.wrapping_mul(magic);
+        value = value.rotate_left(7);
+        value ^= magic;
+
+        chunk.copy_from_slice(&value.to_le_bytes());
+    }
+}
+
 impl NekoHash for MagicalHash {
-    fn hash(&self, data: &[u8]) -> Vec<u8> {
-        let mut result = vec![0u8; 16];
-        let mut rng = StdRng::seed_from_u64(self.magic as u64);
-
-        // Initialize result with magic number
-        for i in 0..4 {
-            let magic_bytes = self.magic.to_le_bytes();
-            result[i*4..(i+1)*4].copy_from_slice(&magic_bytes);
+    type Hasher = MagicalHasher;
+
+    fn hasher(&self) -> MagicalHasher {
+        let mut state = [0u8; 16];
+
+        // Initialize state with magic number
+        for chunk in state.chunks_mut(4) {
+            chunk.copy_from_slice(&self.magic.to_le_bytes());
+        }
+
+        // Mask the lanes with secret bytes drawn ahead of the mixing constants
+        let mut schedule = self.schedule.clone();
+        if self.key.is_some() {
+            for byte in state.iter_mut() {
+                *byte ^= schedule.next_u8();
+            }
         }
 
+        MagicalHasher {
+            magic: self.magic,
+            version: self.version,
+            state,
+            initial_state: state,
+            len: 0,
+            rng: schedule.clone(),
+            schedule,
+        }
+    }
+
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::Magical
+    }
+
+    fn version(&self) -> Version {
+        self.version
+    }
+
+    fn output_size(&self) -> usize {
+        16
+    }
+}
+
+/// Incremental MagicalHash state, created by [`MagicalHash::hasher`]
+#[derive(Clone, Debug)]
+pub struct MagicalHasher {
+    magic: u32,
+    version: Version,
+    state: [u8; 16],
+    initial_state: [u8; 16],
+    len: u64,
+    rng: NekoRng,
+    schedule: NekoRng,
+}
+
+impl Default for MagicalHasher {
+    fn default() -> Self {
+        MagicalHash::new().hasher()
+    }
+}
+
+impl NekoHasher for MagicalHasher {
+    fn update(&mut self, data: &[u8]) {
         // Mix in input data
-        for (i, &byte) in data.iter().enumerate() {
-            let idx = i % 16;
-            result[idx] ^= byte;
-            result[idx] = result[idx].rotate_left(3);
-            
-            let random = rng.gen::<u8>();
-            result[idx] = result[idx].wrapping_add(random);
+        for &byte in data {
+            let idx = (self.len % 16) as usize;
+            self.state[idx] ^= byte;
+            self.state[idx] = self.state[idx].rotate_left(3);
+
+            let random = self.rng.next_u8();
+            self.state[idx] = self.state[idx].wrapping_add(random);
+            self.len += 1;
+
+            // V2 diffuses every completed block across all lanes
+            if self.version == Version::V2 && idx == 15 {
+                let magic = self.lane_magic();
+                transform(&mut self.state, magic);
+                for i in 1..16 {
+                    self.state[i] ^= self.state[i - 1];
+                }
+            }
         }
+    }
 
-        // Apply magical transformations
-        for i in 0..4 {
-            let mut value = u32::from_le_bytes([
-                result[i*4],
-                result[i*4 + 1],
-                result[i*4 + 2],
-                result[i*4 + 3],
-            ]);
-
-            value = value.wrapping_mul(self.magic);
-            value = value.rotate_left(7);
-            value ^= self.magic;
-
-            let bytes = value.to_le_bytes();
-            result[i*4..(i+1)*4].copy_from_slice(&bytes);
+    fn finalize_into(mut self, out: &mut [u8]) {
+        assert_eq!(out.len(), 16, "output buffer must match the hash output size");
+
+        if self.version == Version::V2 {
+            // Pad with 0x80 followed by the little-endian message length
+            let len = self.len;
+            self.update(&[0x80]);
+            self.update(&len.to_le_bytes());
         }
 
+        // Apply magical transformations
+        let mut result = self.state;
+        transform(&mut result, self.lane_magic());
+
         // Final mixing
-        for i in 0..16 {
-            let random = rng.gen::<u8>();
-            result[i] = result[i].wrapping_add(random);
-            result[i] = result[i].rotate_left(3);
+        for byte in result.iter_mut() {
+            let random = self.rng.next_u8();
+            *byte = byte.wrapping_add(random);
+            *byte = byte.rotate_left(3);
         }
 
-        result
+        out.copy_from_slice(&result);
     }
 
     fn output_size(&self) -> usize {
@@ -83,7 +228,30 @@ impl NekoHash for MagicalHash {
     }
 
     fn reset(&mut self) {
-        self.rng = StdRng::seed_from_u64(self.magic as u64);
+        self.state = self.initial_state;
+        self.len = 0;
+        self.rng = self.schedule.clone();
+    }
+}
+
+impl MagicalHasher {
+    /// Returns the lane constant, forced odd under v2 so the lane
+    /// multiplication is invertible and drops no input bits
+    fn lane_magic(&self) -> u32 {
+        match self.version {
+            Version::V1 => self.magic,
+            Version::V2 => self.magic | 1,
+        }
+    }
+}
+
+impl Hasher for MagicalHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        utils::digest_to_u64(&self.clone().finalize())
     }
 }
 
@@ -115,4 +283,140 @@ mod tests {
         let hash2 = hasher.hash(input);
         assert_eq!(hash1, hash2);
     }
+
+    #[test]
+    fn test_magical_hash_array() {
+        let hasher = MagicalHash::new();
+        let input = b"Hello, World!";
+        assert_eq!(hasher.hash_array(input).to_vec(), hasher.hash(input));
+    }
+
+    #[test]
+    fn test_magical_streaming_matches_oneshot() {
+        let hasher = MagicalHash::new();
+        let input: Vec<u8> = (0..100u8).collect();
+        let expected = hasher.hash(&input);
+
+        let mut state = hasher.hasher();
+        for chunk in input.chunks(3) {
+            state.update(chunk);
+        }
+        assert_eq!(state.finalize(), expected);
+
+        let mut state = hasher.hasher();
+        state.update(b"garbage");
+        state.reset();
+        state.update(&input);
+        assert_eq!(state.finalize(), expected);
+    }
+
+    #[test]
+    fn test_magical_golden_vectors() {
+        // Pinned outputs: any change here breaks every stored digest
+        assert_eq!(
+            utils::to_hex(&MagicalHash::new().hash(b"")),
+            "a6572b156f5d7a63f597841206dc9601"
+        );
+        assert_eq!(
+            utils::to_hex(&MagicalHash::new().hash(b"Hello, World!")),
+            "9d1f63d43e2a710757b146d8a2f2c1d2"
+        );
+        assert_eq!(
+            utils::to_hex(&MagicalHash::with_magic(0xCAFEBABE).hash(b"Hello, World!")),
+            "11e20136c98611de4bd05c996131ce8b"
+        );
+        assert_eq!(
+            utils::to_hex(&MagicalHash::with_version(Version::V2).hash(b"Hello, World!")),
+            "835c74529e61abbbabfe7f16a92b3e80"
+        );
+    }
+
+    #[test]
+    fn test_magical_v2_absorption() {
+        let v1 = MagicalHash::new();
+        let v2 = MagicalHash::with_version(Version::V2);
+
+        assert_ne!(v2.hash(b"a"), v2.hash(b"a\0"));
+        assert_ne!(v2.hash(b""), v2.hash(b"\0"));
+
+        // Swapping whole blocks must change the digest
+        let a = [0x11u8; 16];
+        let b = [0x22u8; 16];
+        assert_ne!(v2.hash(&[a, b].concat()), v2.hash(&[b, a].concat()));
+
+        assert_ne!(v1.hash(b"Hello, World!"), v2.hash(b"Hello, World!"));
+
+        // The default magic is even; top lane bits must survive the block mix
+        let mut flipped = [0u8; 16];
+        flipped[3] = 0x10;
+        assert_ne!(v2.hash(&[0u8; 16]), v2.hash(&flipped));
+    }
+
+    #[test]
+    fn test_magical_v2_streaming_matches_oneshot() {
+        let hasher = MagicalHash::with_version(Version::V2);
+        let input: Vec<u8> = (0..100u8).collect();
+        let expected = hasher.hash(&input);
+
+        for split in [0, 1, 15, 16, 17, 100] {
+            let mut state = hasher.hasher();
+            state.update(&input[..split]);
+            state.update(&input[split..]);
+            assert_eq!(state.finalize(), expected);
+        }
+
+        let mut state = hasher.hasher();
+        state.update(&input);
+        state.reset();
+        state.update(&input);
+        assert_eq!(state.finalize(), expected);
+    }
+
+    #[test]
+    fn test_magical_personalization() {
+        let input = b"Hello, World!";
+        let cache = MagicalHash::with_personalization(b"myapp.cache.v1");
+        let files = MagicalHash::with_personalization(b"myapp.files.v1");
+
+        assert_eq!(cache.hash(input), MagicalHash::with_personalization(b"myapp.cache.v1").hash(input));
+        assert_ne!(cache.hash(input), files.hash(input));
+        assert_ne!(cache.hash(input), MagicalHash::new().hash(input));
+        assert_eq!(MagicalHash::with_personalization(b"").hash(input), MagicalHash::new().hash(input));
+
+        let v2 = MagicalHash::with_version(Version::V2).personalized(b"myapp.cache.v1");
+        let mut state = v2.hasher();
+        state.update(&input[..4]);
+        state.update(&input[4..]);
+        assert_eq!(state.finalize(), v2.hash(input));
+    }
+
+    #[test]
+    fn test_magical_keyed() {
+        let input = b"Hello, World!";
+        let key: [u8; 32] = std::array::from_fn(|i| 0xA0 + i as u8);
+        let mut other = key;
+        other[15] ^= 0x80;
+
+        let keyed = MagicalHash::with_key(&key);
+        assert_ne!(keyed.hash(input), MagicalHash::with_key(&other).hash(input));
+        assert_ne!(keyed.hash(input), MagicalHash::with_version(Version::V2).hash(input));
+        assert_eq!(
+            MagicalHash::new().keyed(&key).personalized(b"context").hash(input),
+            MagicalHash::new().personalized(b"context").keyed(&key).hash(input)
+        );
+        assert_ne!(keyed.hash(b"a"), keyed.hash(b"a\0"));
+
+        // The magic number still drives the lanes once a key is set
+        let custom = MagicalHash::with_magic(0x1234_5678).versioned(Version::V2).keyed(&key);
+        assert_ne!(keyed.hash(input), custom.hash(input));
+        let mut state = custom.hasher();
+        state.update(input);
+        state.reset();
+        state.update(input);
+        assert_eq!(state.finalize(), custom.hash(input));
+
+        let debug = format!("{:?}", keyed);
+        assert!(!debug.contains(&format!("{:?}", key)));
+        assert!(!debug.contains(&utils::to_hex(&key)));
+    }
 }
@@ -0,0 +1,402 @@
+//! HOTP (RFC 4226) and TOTP (RFC 6238) one-time passwords
+//!
+//! HMAC-SHA1 and HMAC-SHA256 codes match standard authenticator apps. The
+//! [`OtpAlgorithm::Neko`] variant uses [`NekoMac`] over v2 [`KawaiiHash`] and is
+//! only understood by this crate.
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::Sha256;
+use crate::{KawaiiHash, NekoError, NekoMac, NekoResult, Version, utils};
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// MAC used to compute one-time passwords
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OtpAlgorithm {
+    /// HMAC-SHA1, the RFC 4226 default
+    #[default]
+    Sha1,
+    /// HMAC-SHA256
+    Sha256,
+    /// NekoMac over v2 KawaiiHash
+    Neko,
+}
+
+impl OtpAlgorithm {
+    /// Returns the name used in `otpauth://` URIs
+    pub fn name(&self) -> &'static str {
+        match self {
+            OtpAlgorithm::Sha1 => "SHA1",
+            OtpAlgorithm::Sha256 => "SHA256",
+            OtpAlgorithm::Neko => "NEKO",
+        }
+    }
+
+    fn sign(&self, secret: &[u8], message: &[u8]) -> Vec<u8> {
+        match self {
+            OtpAlgorithm::Sha1 => {
+                let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+                mac.update(message);
+                mac.finalize().into_bytes().to_vec()
+            }
+            OtpAlgorithm::Sha256 => {
+                let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret).expect("HMAC accepts any key length");
+                mac.update(message);
+                mac.finalize().into_bytes().to_vec()
+            }
+            OtpAlgorithm::Neko => NekoMac::new(KawaiiHash::with_version(Version::V2), secret)
+                .expect("v2 KawaiiHash is accepted by NekoMac")
+                .sign(message),
+        }
+    }
+}
+
+impl fmt::Display for OtpAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Counter-based one-time password generator (RFC 4226)
+#[derive(Clone)]
+pub struct Hotp {
+    secret: Vec<u8>,
+    algorithm: OtpAlgorithm,
+    digits: u32,
+}
+
+impl Hotp {
+    /// Creates a 6-digit HMAC-SHA1 generator for `secret`
+    pub fn new(secret: &[u8]) -> NekoResult<Self> {
+        if secret.is_empty() {
+            return Err(NekoError::KeyError("OTP secret cannot be empty".into()));
+        }
+        Ok(Self {
+            secret: secret.to_vec(),
+            algorithm: OtpAlgorithm::default(),
+            digits: 6,
+        })
+    }
+
+    /// Switches the MAC used to compute codes
+    pub fn with_algorithm(mut self, algorithm: OtpAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Sets the code length, which must be 6, 7 or 8 digits
+    pub fn with_digits(mut self, digits: u32) -> NekoResult<Self> {
+        if !(6..=8).contains(&digits) {
+            return Err(NekoError::InvalidInput("OTP codes must have 6 to 8 digits".into()));
+        }
+        self.digits = digits;
+        Ok(self)
+    }
+
+    /// Returns the MAC used to compute codes
+    pub fn algorithm(&self) -> OtpAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns the code length in digits
+    pub fn digits(&self) -> u32 {
+        self.digits
+    }
+
+    /// Computes the code for `counter`
+    pub fn generate(&self, counter: u64) -> String {
+        let mac = self.algorithm.sign(&self.secret, &counter.to_be_bytes());
+
+        // Dynamic truncation
+        let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([mac[offset], mac[offset + 1], mac[offset + 2], mac[offset + 3]]) & 0x7fff_ffff;
+        let code = binary % 10u32.pow(self.digits);
+
+        format!("{:0width$}", code, width = self.digits as usize)
+    }
+
+    /// Checks `code` against counters `counter..=counter + look_ahead`
+    ///
+    /// Returns the matching counter so the caller can resynchronize; the next
+    /// expected counter is one past it.
+    pub fn verify(&self, code: &str, counter: u64, look_ahead: u64) -> Option<u64> {
+        let mut matched = None;
+        for candidate in counter..=counter.saturating_add(look_ahead) {
+            let expected = self.generate(candidate);
+            if utils::constant_time_compare(expected.as_bytes(), code.as_bytes()) && matched.is_none() {
+                matched = Some(candidate);
+            }
+        }
+        matched
+    }
+
+    /// Builds an `otpauth://hotp/` provisioning URI starting at `counter`
+    pub fn to_uri(&self, issuer: &str, account: &str, counter: u64) -> String {
+        format!("{}&counter={}", self.uri("hotp", issuer, account), counter)
+    }
+
+    fn uri(&self, kind: &str, issuer: &str, account: &str) -> String {
+        let issuer = percent_encode(issuer);
+        format!(
+            "otpauth://{}/{}:{}?secret={}&issuer={}&algorithm={}&digits={}",
+            kind,
+            issuer,
+            percent_encode(account),
+            base32_encode(&self.secret),
+            issuer,
+            self.algorithm,
+            self.digits
+        )
+    }
+}
+
+impl fmt::Debug for Hotp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hotp")
+            .field("secret", &"<redacted>")
+            .field("algorithm", &self.algorithm)
+            .field("digits", &self.digits)
+            .finish()
+    }
+}
+
+/// Time-based one-time password generator (RFC 6238)
+#[derive(Clone, Debug)]
+pub struct Totp {
+    hotp: Hotp,
+    step: u64,
+    t0: u64,
+    skew: u64,
+}
+
+impl Totp {
+    /// Creates a 6-digit HMAC-SHA1 generator with 30-second steps, T0 of 0
+    /// and one step of allowed clock skew
+    pub fn new(secret: &[u8]) -> NekoResult<Self> {
+        Ok(Self {
+            hotp: Hotp::new(secret)?,
+            step: 30,
+            t0: 0,
+            skew: 1,
+        })
+    }
+
+    /// Switches the MAC used to compute codes
+    pub fn with_algorithm(mut self, algorithm: OtpAlgorithm) -> Self {
+        self.hotp = self.hotp.with_algorithm(algorithm);
+        self
+    }
+
+    /// Sets the code length, which must be 6, 7 or 8 digits
+    pub fn with_digits(mut self, digits: u32) -> NekoResult<Self> {
+        self.hotp = self.hotp.with_digits(digits)?;
+        Ok(self)
+    }
+
+    /// Sets the time step in seconds
+    pub fn with_step(mut self, step: u64) -> NekoResult<Self> {
+        if step == 0 {
+            return Err(NekoError::InvalidInput("Time step must be greater than 0".into()));
+        }
+        self.step = step;
+        Ok(self)
+    }
+
+    /// Sets the Unix time at which counting starts
+    pub fn with_t0(mut self, t0: u64) -> Self {
+        self.t0 = t0;
+        self
+    }
+
+    /// Sets how many steps either side of the current one are accepted
+    pub fn with_skew(mut self, skew: u64) -> Self {
+        self.skew = skew;
+        self
+    }
+
+    /// Returns the time step counter for Unix time `time`
+    pub fn counter_at(&self, time: u64) -> u64 {
+        time.saturating_sub(self.t0) / self.step
+    }
+
+    /// Computes the code for Unix time `time`
+    pub fn generate_at(&self, time: u64) -> String {
+        self.hotp.generate(self.counter_at(time))
+    }
+
+    /// Computes the code for the current time
+    pub fn generate(&self) -> NekoResult<String> {
+        Ok(self.generate_at(now()?))
+    }
+
+    /// Checks `code` at Unix time `time`, accepting steps within the skew window
+    pub fn verify_at(&self, code: &str, time: u64) -> bool {
+        let counter = self.counter_at(time);
+        let start = counter.saturating_sub(self.skew);
+        let window = counter - start + self.skew;
+        self.hotp.verify(code, start, window).is_some()
+    }
+
+    /// Checks `code` at the current time, accepting steps within the skew window
+    pub fn verify(&self, code: &str) -> NekoResult<bool> {
+        Ok(self.verify_at(code, now()?))
+    }
+
+    /// Builds an `otpauth://totp/` provisioning URI
+    ///
+    /// The URI format has no field for T0, so only generators counting from
+    /// the Unix epoch can be provisioned this way.
+    pub fn to_uri(&self, issuer: &str, account: &str) -> String {
+        format!("{}&period={}", self.hotp.uri("totp", issuer, account), self.step)
+    }
+}
+
+fn now() -> NekoResult<u64> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| NekoError::HashError(format!("System time error: {}", e)))?
+        .as_secs())
+}
+
+/// RFC 4648 base32 without padding, as expected by authenticator apps
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA1_SECRET: &[u8] = b"12345678901234567890";
+    const SHA256_SECRET: &[u8] = b"12345678901234567890123456789012";
+
+    #[test]
+    fn test_rfc4226_vectors() {
+        let hotp = Hotp::new(SHA1_SECRET).unwrap();
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314",
+            "254676", "287922", "162583", "399871", "520489",
+        ];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp.generate(counter as u64), *code);
+        }
+    }
+
+    #[test]
+    fn test_rfc6238_vectors() {
+        let sha1 = Totp::new(SHA1_SECRET).unwrap().with_digits(8).unwrap();
+        let sha256 = Totp::new(SHA256_SECRET).unwrap()
+            .with_algorithm(OtpAlgorithm::Sha256)
+            .with_digits(8)
+            .unwrap();
+        for (time, sha1_code, sha256_code) in [
+            (59, "94287082", "46119246"),
+            (1111111109, "07081804", "68084774"),
+            (1111111111, "14050471", "67062674"),
+            (1234567890, "89005924", "91819424"),
+            (2000000000, "69279037", "90698825"),
+            (20000000000, "65353130", "77737706"),
+        ] {
+            assert_eq!(sha1.generate_at(time), sha1_code);
+            assert_eq!(sha256.generate_at(time), sha256_code);
+        }
+    }
+
+    #[test]
+    fn test_neko_variant() {
+        let neko = Hotp::new(SHA1_SECRET).unwrap().with_algorithm(OtpAlgorithm::Neko);
+        let code = neko.generate(0);
+        assert_eq!(code.len(), 6);
+        assert!(code.bytes().all(|b| b.is_ascii_digit()));
+        assert_eq!(code, neko.generate(0));
+        assert_ne!(code, Hotp::new(SHA1_SECRET).unwrap().generate(0));
+    }
+
+    #[test]
+    fn test_hotp_verify_look_ahead() {
+        let hotp = Hotp::new(SHA1_SECRET).unwrap();
+        assert_eq!(hotp.verify("969429", 0, 5), Some(3));
+        assert_eq!(hotp.verify("969429", 0, 2), None);
+        assert_eq!(hotp.verify("96942", 0, 5), None);
+    }
+
+    #[test]
+    fn test_totp_skew_window() {
+        let totp = Totp::new(SHA1_SECRET).unwrap();
+        let code = totp.generate_at(1_000_000);
+        assert!(totp.verify_at(&code, 1_000_000));
+        assert!(totp.verify_at(&code, 1_000_000 + 30));
+        assert!(totp.verify_at(&code, 1_000_000 - 30));
+        assert!(!totp.verify_at(&code, 1_000_000 + 60));
+        assert!(!totp.clone().with_skew(0).verify_at(&code, 1_000_000 + 30));
+
+        // Skew window is clamped at the first step
+        let first = totp.generate_at(0);
+        assert!(totp.verify_at(&first, 0));
+        assert!(totp.verify_at(&first, 30));
+
+        let shifted = totp.clone().with_t0(30);
+        assert_eq!(shifted.generate_at(30), first);
+        assert_eq!(totp.clone().with_step(60).unwrap().counter_at(120), 2);
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert!(Hotp::new(&[]).is_err());
+        assert!(Hotp::new(SHA1_SECRET).unwrap().with_digits(5).is_err());
+        assert!(Hotp::new(SHA1_SECRET).unwrap().with_digits(9).is_err());
+        assert!(Totp::new(SHA1_SECRET).unwrap().with_step(0).is_err());
+    }
+
+    #[test]
+    fn test_uri() {
+        let totp = Totp::new(b"Hello!\xde\xad\xbe\xef").unwrap()
+            .with_algorithm(OtpAlgorithm::Sha256)
+            .with_digits(8)
+            .unwrap();
+        assert_eq!(
+            totp.to_uri("Neko Corp", "alice@example.com"),
+            "otpauth://totp/Neko%20Corp:alice%40example.com?secret=JBSWY3DPEHPK3PXP\
+             &issuer=Neko%20Corp&algorithm=SHA256&digits=8&period=30"
+        );
+
+        let hotp = Hotp::new(b"f").unwrap();
+        assert_eq!(hotp.to_uri("Neko", "bob", 7), "otpauth://hotp/Neko:bob?secret=MY&issuer=Neko&algorithm=SHA1&digits=6&counter=7");
+    }
+
+    #[test]
+    fn test_debug_redacts_secret() {
+        let debug = format!("{:?}", Totp::new(b"super secret").unwrap());
+        assert!(!debug.contains("super"));
+        assert!(debug.contains("<redacted>"));
+    }
+}
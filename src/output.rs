@@ -0,0 +1,239 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use crate::{NekoError, NekoResult, utils};
+
+/// Identifies the algorithm that produced a digest
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Algorithm {
+    /// KawaiiHash
+    Kawaii,
+    /// MagicalHash
+    Magical,
+    /// TsundereHash
+    Tsundere,
+}
+
+impl Algorithm {
+    /// Returns the lowercase name used in textual digests
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Kawaii => "kawaii",
+            Algorithm::Magical => "magical",
+            Algorithm::Tsundere => "tsundere",
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = NekoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "kawaii" => Ok(Algorithm::Kawaii),
+            "magical" => Ok(Algorithm::Magical),
+            "tsundere" => Ok(Algorithm::Tsundere),
+            _ => Err(NekoError::EncodingError(format!("Unknown algorithm: {}", s))),
+        }
+    }
+}
+
+/// Hash output tagged with the algorithm that produced it
+///
+/// Equality is checked in constant time. The textual form is
+/// `<algorithm>:<hex>`, e.g. `tsundere:3fa2...`; use `{:x}` for bare hex.
+#[derive(Clone, Debug)]
+pub struct NekoDigest {
+    algorithm: Algorithm,
+    bytes: Vec<u8>,
+}
+
+impl NekoDigest {
+    /// Wraps raw digest bytes produced by `algorithm`
+    pub fn new(algorithm: Algorithm, bytes: Vec<u8>) -> Self {
+        Self { algorithm, bytes }
+    }
+
+    /// Returns the algorithm that produced this digest
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Returns the raw digest bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Consumes the digest and returns the raw bytes
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Returns the digest length in bytes
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns true if the digest has no bytes
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl AsRef<[u8]> for NekoDigest {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl fmt::LowerHex for NekoDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&utils::to_hex(&self.bytes))
+    }
+}
+
+impl fmt::Display for NekoDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{:x}", self.algorithm, self)
+    }
+}
+
+impl FromStr for NekoDigest {
+    type Err = NekoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, hex) = s
+            .split_once(':')
+            .ok_or_else(|| NekoError::EncodingError("Digest must be formatted as <algorithm>:<hex>".into()))?;
+        Ok(Self::new(algorithm.parse()?, utils::from_hex(hex)?))
+    }
+}
+
+impl PartialEq for NekoDigest {
+    fn eq(&self, other: &Self) -> bool {
+        self.algorithm == other.algorithm && utils::constant_time_compare(&self.bytes, &other.bytes)
+    }
+}
+
+impl Eq for NekoDigest {}
+
+impl PartialOrd for NekoDigest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NekoDigest {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.algorithm
+            .cmp(&other.algorithm)
+            .then_with(|| self.bytes.cmp(&other.bytes))
+    }
+}
+
+impl Hash for NekoDigest {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.algorithm.hash(state);
+        self.bytes.hash(state);
+    }
+}
+
+/// Encrypted hash output together with the key that decrypts it
+///
+/// The ciphertext is the base64 output of [`utils::encrypt_data`]. When no key
+/// is supplied to [`crate::NekoHash::hash_encrypted`] a fresh one is generated
+/// and returned here, so store it or the hash cannot be recovered.
+#[derive(Clone)]
+pub struct EncryptedHash {
+    ciphertext: Vec<u8>,
+    key: Vec<u8>,
+}
+
+impl EncryptedHash {
+    /// Bundles ciphertext with the key used to produce it
+    pub fn new(ciphertext: Vec<u8>, key: Vec<u8>) -> Self {
+        Self { ciphertext, key }
+    }
+
+    /// Returns the base64 ciphertext
+    pub fn ciphertext(&self) -> &[u8] {
+        &self.ciphertext
+    }
+
+    /// Returns the 32-byte encryption key
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Decrypts the hash with the bundled key
+    pub fn decrypt(&self) -> NekoResult<Vec<u8>> {
+        utils::decrypt_data(&self.ciphertext, &self.key)
+    }
+
+    /// Consumes the bundle and returns the ciphertext and key
+    pub fn into_parts(self) -> (Vec<u8>, Vec<u8>) {
+        (self.ciphertext, self.key)
+    }
+}
+
+impl fmt::Debug for EncryptedHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedHash")
+            .field("ciphertext", &String::from_utf8_lossy(&self.ciphertext))
+            .field("key", &"<redacted>")
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{KawaiiHash, MagicalHash, NekoHash, TsundereHash};
+
+    #[test]
+    fn test_digest_round_trip() {
+        let digest = TsundereHash::new().digest(b"Hello, World!");
+        assert_eq!(digest.algorithm(), Algorithm::Tsundere);
+        assert_eq!(digest.as_bytes(), TsundereHash::new().hash(b"Hello, World!").as_slice());
+
+        let text = digest.to_string();
+        assert!(text.starts_with("tsundere:"));
+        assert_eq!(format!("{:x}", digest), utils::to_hex(digest.as_ref()));
+        assert_eq!(text.parse::<NekoDigest>().unwrap(), digest);
+
+        assert!("tsundere".parse::<NekoDigest>().is_err());
+        assert!("sha256:00".parse::<NekoDigest>().is_err());
+        assert!("kawaii:zz".parse::<NekoDigest>().is_err());
+        assert!("kawaii:aéa".parse::<NekoDigest>().is_err());
+    }
+
+    #[test]
+    fn test_digest_equality_includes_algorithm() {
+        let a = NekoDigest::new(Algorithm::Kawaii, vec![1, 2, 3]);
+        let b = NekoDigest::new(Algorithm::Magical, vec![1, 2, 3]);
+        assert_ne!(a, b);
+        assert!(a < b);
+        assert_eq!(a, NekoDigest::new(Algorithm::Kawaii, vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn test_verify() {
+        let hasher = MagicalHash::new();
+        let expected = hasher.digest(b"Hello, World!");
+        assert!(hasher.verify(b"Hello, World!", &expected));
+        assert!(!hasher.verify(b"Hello, World?", &expected));
+
+        // Same bytes from another algorithm must not verify
+        let kawaii = KawaiiHash::with_size(16);
+        let forged = NekoDigest::new(Algorithm::Kawaii, expected.as_bytes().to_vec());
+        assert!(!hasher.verify(b"Hello, World!", &forged));
+        assert!(!kawaii.verify(b"Hello, World!", &expected));
+    }
+}
@@ -0,0 +1,244 @@
+//! Password hashing with self-describing PHC-style strings
+//!
+//! Hashes look like `$neko-kawaii$i=10000$<salt>$<hash>`, with salt and hash
+//! in unpadded base64, so the cost and salt always travel with the hash.
+//! The password, salt and optional pepper are stretched with v2 [`KawaiiHash`].
+
+use std::fmt;
+use base64::{Engine as _, engine::general_purpose::STANDARD_NO_PAD as BASE64};
+use rand::{Rng, thread_rng};
+use crate::{KawaiiHash, NekoError, NekoHash, NekoMac, NekoResult, Version, utils};
+
+/// Algorithm identifier written into password hash strings
+pub const ALGORITHM_ID: &str = "neko-kawaii";
+
+/// Highest iteration count accepted, so a stored hash string cannot demand unbounded work
+pub const MAX_ITERATIONS: usize = 100_000;
+
+const HASH_LEN: usize = 32;
+
+/// Cost and pepper settings for password hashing
+#[derive(Clone)]
+pub struct PasswordPolicy {
+    /// Number of KawaiiHash iterations
+    pub iterations: usize,
+    /// Salt length in bytes
+    pub salt_len: usize,
+    /// Server-side secret mixed into every password, never stored in the hash string
+    pub pepper: Option<Vec<u8>>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            iterations: 10000,
+            salt_len: 16,
+            pepper: None,
+        }
+    }
+}
+
+impl fmt::Debug for PasswordPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PasswordPolicy")
+            .field("iterations", &self.iterations)
+            .field("salt_len", &self.salt_len)
+            .field("pepper", &self.pepper.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+/// Parsed form of a password hash string
+struct PasswordHash {
+    iterations: usize,
+    salt: Vec<u8>,
+    hash: Vec<u8>,
+}
+
+impl PasswordHash {
+    fn parse(phc: &str) -> NekoResult<Self> {
+        let parts: Vec<&str> = phc.split('$').collect();
+        if parts.len() != 5 || !parts[0].is_empty() {
+            return Err(NekoError::EncodingError("Malformed password hash string".into()));
+        }
+        if parts[1] != ALGORITHM_ID {
+            return Err(NekoError::EncodingError(format!("Unsupported password hash algorithm: {}", parts[1])));
+        }
+
+        let iterations = parts[2]
+            .strip_prefix("i=")
+            .and_then(|i| i.parse::<usize>().ok())
+            .filter(|i| (1..=MAX_ITERATIONS).contains(i))
+            .ok_or_else(|| NekoError::EncodingError("Invalid iteration parameter".into()))?;
+        let salt = BASE64.decode(parts[3])
+            .map_err(|e| NekoError::EncodingError(format!("Invalid salt: {}", e)))?;
+        let hash = BASE64.decode(parts[4])
+            .map_err(|e| NekoError::EncodingError(format!("Invalid hash: {}", e)))?;
+
+        Ok(Self { iterations, salt, hash })
+    }
+}
+
+impl fmt::Display for PasswordHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "${}$i={}${}${}",
+            ALGORITHM_ID,
+            self.iterations,
+            BASE64.encode(&self.salt),
+            BASE64.encode(&self.hash)
+        )
+    }
+}
+
+fn compute(password: &[u8], salt: &[u8], iterations: usize, pepper: Option<&[u8]>) -> NekoResult<Vec<u8>> {
+    if password.is_empty() {
+        return Err(NekoError::InvalidInput("Password cannot be empty".into()));
+    }
+
+    let hash = KawaiiHash::with_size(HASH_LEN).versioned(Version::V2);
+    let mut input = match pepper {
+        Some(pepper) => NekoMac::new(hash.clone(), pepper)?.sign(password),
+        None => password.to_vec(),
+    };
+    input.extend_from_slice(salt);
+
+    for _ in 0..iterations {
+        input = hash.hash(&input);
+    }
+    Ok(input)
+}
+
+/// Hashes a password with the default policy
+pub fn hash_password(password: &[u8]) -> NekoResult<String> {
+    hash_password_with_policy(password, &PasswordPolicy::default())
+}
+
+/// Hashes a password with a fresh random salt using the given policy
+pub fn hash_password_with_policy(password: &[u8], policy: &PasswordPolicy) -> NekoResult<String> {
+    if policy.salt_len == 0 {
+        return Err(NekoError::InvalidInput("Salt length must be greater than 0".into()));
+    }
+    if !(1..=MAX_ITERATIONS).contains(&policy.iterations) {
+        return Err(NekoError::InvalidInput(format!("Iterations must be between 1 and {}", MAX_ITERATIONS)));
+    }
+
+    let mut salt = vec![0u8; policy.salt_len];
+    thread_rng().fill(&mut salt[..]);
+
+    let hash = compute(password, &salt, policy.iterations, policy.pepper.as_deref())?;
+    Ok(PasswordHash { iterations: policy.iterations, salt, hash }.to_string())
+}
+
+/// Checks a password against a hash string in constant time
+pub fn verify_password(password: &[u8], phc: &str) -> NekoResult<bool> {
+    verify(password, phc, None)
+}
+
+/// Checks a password hashed under a pepper against a hash string in constant time
+pub fn verify_password_with_pepper(password: &[u8], phc: &str, pepper: &[u8]) -> NekoResult<bool> {
+    verify(password, phc, Some(pepper))
+}
+
+fn verify(password: &[u8], phc: &str, pepper: Option<&[u8]>) -> NekoResult<bool> {
+    let parsed = PasswordHash::parse(phc)?;
+    let hash = compute(password, &parsed.salt, parsed.iterations, pepper)?;
+    Ok(utils::constant_time_compare(&hash, &parsed.hash))
+}
+
+/// Reports whether a stored hash was made with weaker parameters than `policy`
+/// and should be replaced the next time the password is available
+pub fn needs_rehash(phc: &str, policy: &PasswordPolicy) -> NekoResult<bool> {
+    let parsed = PasswordHash::parse(phc)?;
+    Ok(parsed.iterations != policy.iterations
+        || parsed.salt.len() < policy.salt_len
+        || parsed.hash.len() != HASH_LEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fast_policy() -> PasswordPolicy {
+        PasswordPolicy { iterations: 100, ..PasswordPolicy::default() }
+    }
+
+    #[test]
+    fn test_hash_and_verify() {
+        let phc = hash_password_with_policy(b"hunter2", &fast_policy()).unwrap();
+        assert!(phc.starts_with("$neko-kawaii$i=100$"));
+        assert!(verify_password(b"hunter2", &phc).unwrap());
+        assert!(!verify_password(b"hunter3", &phc).unwrap());
+
+        // Fresh salt every time
+        let again = hash_password_with_policy(b"hunter2", &fast_policy()).unwrap();
+        assert_ne!(phc, again);
+
+        assert!(hash_password(b"").is_err());
+    }
+
+    #[test]
+    fn test_default_policy_matches_v2_stretch() {
+        let phc = hash_password(b"hunter2").unwrap();
+        let parsed = PasswordHash::parse(&phc).unwrap();
+        assert_eq!(parsed.iterations, 10000);
+
+        let kawaii = KawaiiHash::with_version(Version::V2);
+        let expected = (0..10000).fold([&b"hunter2"[..], &parsed.salt].concat(), |acc, _| kawaii.hash(&acc));
+        assert_eq!(parsed.hash, expected);
+    }
+
+    #[test]
+    fn test_swapped_bytes_rejected() {
+        let mut password = [b'x'; 33];
+        password[0] = b'A';
+        password[32] = b'B';
+        let phc = hash_password_with_policy(&password, &fast_policy()).unwrap();
+
+        password.swap(0, 32);
+        assert!(!verify_password(&password, &phc).unwrap());
+    }
+
+    #[test]
+    fn test_pepper() {
+        let policy = PasswordPolicy { pepper: Some(b"server secret".to_vec()), ..fast_policy() };
+        let phc = hash_password_with_policy(b"hunter2", &policy).unwrap();
+
+        assert!(verify_password_with_pepper(b"hunter2", &phc, b"server secret").unwrap());
+        assert!(!verify_password_with_pepper(b"hunter2", &phc, b"wrong secret").unwrap());
+        assert!(!verify_password(b"hunter2", &phc).unwrap());
+    }
+
+    #[test]
+    fn test_needs_rehash() {
+        let phc = hash_password_with_policy(b"hunter2", &fast_policy()).unwrap();
+        assert!(!needs_rehash(&phc, &fast_policy()).unwrap());
+        assert!(needs_rehash(&phc, &PasswordPolicy { iterations: 200, ..fast_policy() }).unwrap());
+        assert!(needs_rehash(&phc, &PasswordPolicy { salt_len: 32, ..fast_policy() }).unwrap());
+    }
+
+    #[test]
+    fn test_iteration_cap() {
+        let policy = PasswordPolicy { iterations: MAX_ITERATIONS + 1, ..fast_policy() };
+        assert!(matches!(hash_password_with_policy(b"hunter2", &policy), Err(NekoError::InvalidInput(_))));
+        let policy = PasswordPolicy { iterations: 0, ..fast_policy() };
+        assert!(hash_password_with_policy(b"hunter2", &policy).is_err());
+    }
+
+    #[test]
+    fn test_malformed_strings() {
+        for phc in [
+            "",
+            "$neko-kawaii$i=100$c2FsdA",
+            "$argon2id$i=100$c2FsdA$aGFzaA",
+            "$neko-kawaii$n=100$c2FsdA$aGFzaA",
+            "$neko-kawaii$i=0$c2FsdA$aGFzaA",
+            "$neko-kawaii$i=100001$c2FsdA$aGFzaA",
+            "$neko-kawaii$i=18446744073709551615$c2FsdA$aGFzaA",
+            "$neko-kawaii$i=100$!!!$aGFzaA",
+        ] {
+            assert!(verify_password(b"hunter2", phc).is_err(), "{}", phc);
+        }
+    }
+}
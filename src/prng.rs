@@ -0,0 +1,160 @@
+//! Deterministic constant schedule shared by the hash algorithms
+//!
+//! The schedule is the ChaCha stream cipher reduced to 12 rounds, keyed by
+//! expanding a 64-bit seed with the PCG32 output function, read one 32-bit
+//! word at a time. This is bit-for-bit the stream `rand` 0.8 produced from
+//! `StdRng::seed_from_u64`, which the algorithms originally used, so digests
+//! computed before the schedule was specified here remain valid. Keeping the
+//! definition in-tree means dependency upgrades can never change a digest.
+
+use std::fmt;
+use crate::{KawaiiHash, NekoHash, NekoHasher, Version};
+
+/// ChaCha12 keystream generator used to draw mixing constants
+#[derive(Clone)]
+pub(crate) struct NekoRng {
+    key: [u32; 8],
+    counter: u64,
+    block: [u32; 16],
+    index: usize,
+}
+
+const CHACHA_CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+const CHACHA_ROUNDS: usize = 12;
+
+impl NekoRng {
+    /// Creates a generator from a 32-byte key
+    pub(crate) fn from_key(key: &[u8; 32]) -> Self {
+        let mut words = [0u32; 8];
+        for (word, chunk) in words.iter_mut().zip(key.chunks_exact(4)) {
+            *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        Self {
+            key: words,
+            counter: 0,
+            block: [0; 16],
+            index: 16,
+        }
+    }
+
+    /// Creates a generator by expanding a 64-bit seed into a key with PCG32
+    pub(crate) fn from_seed(mut state: u64) -> Self {
+        const MUL: u64 = 6364136223846793005;
+        const INC: u64 = 11634580027462260723;
+
+        let mut key = [0u8; 32];
+        for chunk in key.chunks_exact_mut(4) {
+            state = state.wrapping_mul(MUL).wrapping_add(INC);
+            let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+            let rot = (state >> 59) as u32;
+            chunk.copy_from_slice(&xorshifted.rotate_right(rot).to_le_bytes());
+        }
+        Self::from_key(&key)
+    }
+
+    /// Derives a generator whose stream is bound to a personalization string
+    ///
+    /// The new key is the v2 KawaiiHash of a domain tag, the current key and
+    /// the personalization, so distinct strings yield unrelated streams.
+    pub(crate) fn personalize(&self, personalization: &[u8]) -> Self {
+        let mut hasher = KawaiiHash::with_version(Version::V2).hasher();
+        hasher.update(b"nekohash.personalization");
+        for word in &self.key {
+            hasher.update(&word.to_le_bytes());
+        }
+        hasher.update(personalization);
+
+        let mut key = [0u8; 32];
+        hasher.finalize_into(&mut key);
+        Self::from_key(&key)
+    }
+
+    /// Returns the next 32-bit word of the stream
+    pub(crate) fn next_u32(&mut self) -> u32 {
+        if self.index == 16 {
+            self.refill();
+        }
+        let word = self.block[self.index];
+        self.index += 1;
+        word
+    }
+
+    /// Returns the low byte of the next 32-bit word of the stream
+    pub(crate) fn next_u8(&mut self) -> u8 {
+        self.next_u32() as u8
+    }
+
+    /// Fills `out` with bytes drawn via [`NekoRng::next_u8`]
+    pub(crate) fn fill_bytes(&mut self, out: &mut [u8]) {
+        for byte in out {
+            *byte = self.next_u8();
+        }
+    }
+
+    fn refill(&mut self) {
+        let mut input = [0u32; 16];
+        input[..4].copy_from_slice(&CHACHA_CONSTANTS);
+        input[4..12].copy_from_slice(&self.key);
+        input[12] = self.counter as u32;
+        input[13] = (self.counter >> 32) as u32;
+
+        let mut x = input;
+        for _ in 0..CHACHA_ROUNDS / 2 {
+            quarter_round(&mut x, 0, 4, 8, 12);
+            quarter_round(&mut x, 1, 5, 9, 13);
+            quarter_round(&mut x, 2, 6, 10, 14);
+            quarter_round(&mut x, 3, 7, 11, 15);
+            quarter_round(&mut x, 0, 5, 10, 15);
+            quarter_round(&mut x, 1, 6, 11, 12);
+            quarter_round(&mut x, 2, 7, 8, 13);
+            quarter_round(&mut x, 3, 4, 9, 14);
+        }
+
+        for (out, (mixed, original)) in self.block.iter_mut().zip(x.iter().zip(input.iter())) {
+            *out = mixed.wrapping_add(*original);
+        }
+        self.counter = self.counter.wrapping_add(1);
+        self.index = 0;
+    }
+}
+
+// Keyed schedules hold secrets, so never print the key or keystream
+impl fmt::Debug for NekoRng {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NekoRng").finish_non_exhaustive()
+    }
+}
+
+#[inline(always)]
+fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(16);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(12);
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(8);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(7);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_stream_golden() {
+        let mut rng = NekoRng::from_seed(0xDEADBEEF);
+        let words: Vec<u32> = (0..20).map(|_| rng.next_u32()).collect();
+        assert_eq!(words[..4], [0xd379d55f, 0x922ad30e, 0xd7a49e60, 0x1b0ae3df]);
+        // Crosses into the second ChaCha block
+        assert_eq!(words[16..], [0xd3c7b574, 0xcd030d51, 0xc7d1d0fc, 0x01840178]);
+    }
+
+    #[test]
+    fn test_keyed_stream_golden() {
+        let mut rng = NekoRng::from_key(&[0u8; 32]);
+        let words: Vec<u32> = (0..4).map(|_| rng.next_u32()).collect();
+        assert_eq!(words, [0x6a9af49b, 0x53f95507, 0x12ce1f81, 0xd583265f]);
+        assert_eq!(NekoRng::from_key(&[0u8; 32]).next_u8(), 0x9b);
+    }
+}
@@ -0,0 +1,141 @@
+//! Deterministic authenticated encryption (SIV construction)
+//!
+//! The synthetic IV is a [`NekoMac`] over v2 [`KawaiiHash`] of the associated
+//! data and plaintext, truncated to 16 bytes. It both authenticates the
+//! message and serves as the AES-256-CTR IV, so identical plaintexts and
+//! associated data under the same key always encrypt identically. This leaks
+//! equality, which is the point for lookup and deduplication, but nothing
+//! else; use [`crate::utils::encrypt_data`] where equality must stay hidden.
+//!
+//! Output is `siv || ciphertext`.
+
+use std::fmt;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use crate::envelope::{Aes256Ctr64BE, check_key, derive_subkey};
+use crate::{KawaiiHash, NekoError, NekoMac, NekoResult, Version, utils};
+
+/// Size of the synthetic IV in bytes
+pub const SIV_SIZE: usize = 16;
+
+/// Deterministic cipher keyed for repeated use, e.g. one per database column
+#[derive(Clone)]
+pub struct Siv {
+    cipher_key: [u8; 32],
+    mac: NekoMac<KawaiiHash>,
+}
+
+impl Siv {
+    /// Derives independent cipher and MAC keys from a 32-byte key
+    pub fn new(key: &[u8]) -> NekoResult<Self> {
+        check_key(key)?;
+        Ok(Self {
+            cipher_key: derive_subkey(key, b"nekohash.siv.cipher"),
+            mac: NekoMac::new(KawaiiHash::with_version(Version::V2), &derive_subkey(key, b"nekohash.siv.mac"))?,
+        })
+    }
+
+    /// Encrypts `plaintext` bound to `aad`, returning `siv || ciphertext`
+    pub fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Vec<u8> {
+        let siv = self.synthetic_iv(plaintext, aad);
+
+        let mut output = Vec::with_capacity(SIV_SIZE + plaintext.len());
+        output.extend_from_slice(&siv);
+        output.extend_from_slice(plaintext);
+        Aes256Ctr64BE::new(&self.cipher_key.into(), &siv.into()).apply_keystream(&mut output[SIV_SIZE..]);
+        output
+    }
+
+    /// Decrypts `siv || ciphertext` and checks it against `aad`
+    ///
+    /// Returns [`NekoError::CryptoError`] if the key or `aad` is wrong or the
+    /// ciphertext was modified.
+    pub fn decrypt(&self, ciphertext: &[u8], aad: &[u8]) -> NekoResult<Vec<u8>> {
+        if ciphertext.len() < SIV_SIZE {
+            return Err(NekoError::CryptoError("Invalid encrypted data".into()));
+        }
+        let (siv, ciphertext) = ciphertext.split_at(SIV_SIZE);
+
+        let mut plaintext = ciphertext.to_vec();
+        Aes256Ctr64BE::new(&self.cipher_key.into(), siv.into()).apply_keystream(&mut plaintext);
+
+        if !utils::constant_time_compare(&self.synthetic_iv(&plaintext, aad), siv) {
+            return Err(NekoError::CryptoError("Authentication failed".into()));
+        }
+        Ok(plaintext)
+    }
+
+    fn synthetic_iv(&self, plaintext: &[u8], aad: &[u8]) -> [u8; SIV_SIZE] {
+        let mut mac = self.mac.clone();
+        mac.update(&(aad.len() as u64).to_le_bytes());
+        mac.update(aad);
+        mac.update(plaintext);
+
+        let mut siv = [0u8; SIV_SIZE];
+        siv.copy_from_slice(&mac.finalize()[..SIV_SIZE]);
+        siv
+    }
+}
+
+impl fmt::Debug for Siv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Siv").finish_non_exhaustive()
+    }
+}
+
+/// Deterministically encrypts `plaintext` bound to `aad` under a 32-byte key
+pub fn encrypt(plaintext: &[u8], aad: &[u8], key: &[u8]) -> NekoResult<Vec<u8>> {
+    Ok(Siv::new(key)?.encrypt(plaintext, aad))
+}
+
+/// Decrypts output of [`encrypt`], failing on a wrong key, wrong `aad` or tampering
+pub fn decrypt(ciphertext: &[u8], aad: &[u8], key: &[u8]) -> NekoResult<Vec<u8>> {
+    Siv::new(key)?.decrypt(ciphertext, aad)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [42; 32];
+
+    #[test]
+    fn test_deterministic_round_trip() {
+        let a = encrypt(b"alice@example.com", b"users.email", &KEY).unwrap();
+        let b = encrypt(b"alice@example.com", b"users.email", &KEY).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.len(), SIV_SIZE + 17);
+        assert_eq!(decrypt(&a, b"users.email", &KEY).unwrap(), b"alice@example.com");
+
+        assert_ne!(a, encrypt(b"bob@example.com", b"users.email", &KEY).unwrap());
+        assert_ne!(a, encrypt(b"alice@example.com", b"users.backup_email", &KEY).unwrap());
+        assert_ne!(a, encrypt(b"alice@example.com", b"users.email", &[7u8; 32]).unwrap());
+
+        // Trailing zero bytes must not share a synthetic IV
+        let short = encrypt(b"a", b"", &KEY).unwrap();
+        let padded = encrypt(b"a\0", b"", &KEY).unwrap();
+        assert_ne!(short[..SIV_SIZE], padded[..SIV_SIZE]);
+
+        let empty = encrypt(b"", b"", &KEY).unwrap();
+        assert_eq!(decrypt(&empty, b"", &KEY).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_tampering_detected() {
+        let siv = Siv::new(&KEY).unwrap();
+        let ciphertext = siv.encrypt(b"4111111111111111", b"cards");
+
+        for i in [0, SIV_SIZE - 1, SIV_SIZE, ciphertext.len() - 1] {
+            let mut tampered = ciphertext.clone();
+            tampered[i] ^= 1;
+            assert!(matches!(siv.decrypt(&tampered, b"cards"), Err(NekoError::CryptoError(_))));
+        }
+
+        let mut extended = ciphertext.clone();
+        extended.push(0);
+        assert!(siv.decrypt(&extended, b"cards").is_err());
+        assert!(siv.decrypt(&ciphertext[..SIV_SIZE - 1], b"cards").is_err());
+        assert!(siv.decrypt(&ciphertext, b"other").is_err());
+        assert!(decrypt(&ciphertext, b"cards", &[7u8; 32]).is_err());
+        assert!(matches!(Siv::new(&[0u8; 16]), Err(NekoError::KeyError(_))));
+    }
+}
@@ -0,0 +1,608 @@
+//! Chunked streaming encryption (STREAM construction)
+//!
+//! The output is raw binary:
+//!
+//! ```text
+//! magic "NEKS" | version u8 | suite u8 | chunk size u32 LE | nonce prefix (7 bytes) | chunks...
+//! ```
+//!
+//! Each chunk is up to `chunk size` bytes of AES-256-CTR ciphertext followed
+//! by a 32-byte HMAC-SHA256 tag over the header, the chunk nonce and the
+//! ciphertext. The chunk nonce is the prefix, a big-endian chunk counter and
+//! a flag byte set only on the final chunk, so reordered, dropped or
+//! truncated chunks fail authentication. The final chunk may be empty.
+//!
+//! Because each chunk is CTR-encrypted at a known offset, [`SeekableDecryptor`]
+//! can decrypt any byte range after verifying only the chunks it touches.
+//! It reads only this format; single-tag blobs from
+//! [`crate::utils::encrypt_data`] cannot be verified piecewise and must be
+//! decrypted whole.
+
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use ctr::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use hmac::Mac;
+use rand::{Rng, thread_rng};
+use crate::envelope::{Aes256Ctr64BE, CipherSuite, HmacSha256, check_key, derive_subkey, hmac_sha256};
+use crate::{NekoError, NekoResult};
+
+/// Leading bytes of every encrypted stream
+pub const STREAM_MAGIC: &[u8; 4] = b"NEKS";
+/// Stream format version written by [`Encryptor`]
+pub const STREAM_VERSION: u8 = 1;
+/// Plaintext bytes per chunk used by [`Encryptor::new`]
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+const NONCE_PREFIX_SIZE: usize = 7;
+const HEADER_SIZE: usize = 4 + 1 + 1 + 4 + NONCE_PREFIX_SIZE;
+const TAG_SIZE: usize = 32;
+
+/// Largest chunk accepted, so the in-chunk block counter never carries into
+/// the chunk nonce and a crafted header cannot force a huge allocation
+const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Stream header and derived keys shared by the encryptor and decryptors
+#[derive(Clone)]
+struct StreamKeys {
+    header: [u8; HEADER_SIZE],
+    chunk_size: usize,
+    cipher_key: [u8; 32],
+    mac_key: [u8; 32],
+}
+
+impl StreamKeys {
+    fn generate(key: &[u8], chunk_size: usize) -> NekoResult<Self> {
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(NekoError::InvalidInput(format!("Chunk size must be between 1 and {} bytes", MAX_CHUNK_SIZE)));
+        }
+
+        let mut header = [0u8; HEADER_SIZE];
+        header[..4].copy_from_slice(STREAM_MAGIC);
+        header[4] = STREAM_VERSION;
+        header[5] = CipherSuite::Aes256CtrHmacSha256.id();
+        header[6..10].copy_from_slice(&(chunk_size as u32).to_le_bytes());
+        thread_rng().fill(&mut header[10..]);
+
+        Self::from_header(header, key)
+    }
+
+    /// Validates a stream header and derives the keys for it
+    fn from_header(header: [u8; HEADER_SIZE], key: &[u8]) -> NekoResult<Self> {
+        check_key(key)?;
+        if &header[..4] != STREAM_MAGIC {
+            return Err(NekoError::EncodingError("Missing stream magic".into()));
+        }
+        if header[4] != STREAM_VERSION {
+            return Err(NekoError::EncodingError(format!("Unsupported stream version: {}", header[4])));
+        }
+        if CipherSuite::from_id(header[5])? != CipherSuite::Aes256CtrHmacSha256 {
+            return Err(NekoError::EncodingError(format!("Unsupported cipher suite: {}", header[5])));
+        }
+        let chunk_size = u32::from_le_bytes([header[6], header[7], header[8], header[9]]) as usize;
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(NekoError::EncodingError("Invalid stream chunk size".into()));
+        }
+
+        Ok(Self {
+            header,
+            chunk_size,
+            cipher_key: derive_subkey(key, b"nekohash.stream.cipher"),
+            mac_key: derive_subkey(key, b"nekohash.stream.mac"),
+        })
+    }
+
+    /// Returns the 16-byte CTR IV for a chunk: the 12-byte chunk nonce
+    /// followed by a zeroed block counter
+    fn iv(&self, counter: u32, last: bool) -> [u8; 16] {
+        let mut iv = [0u8; 16];
+        iv[..NONCE_PREFIX_SIZE].copy_from_slice(&self.header[10..]);
+        iv[NONCE_PREFIX_SIZE..NONCE_PREFIX_SIZE + 4].copy_from_slice(&counter.to_be_bytes());
+        iv[NONCE_PREFIX_SIZE + 4] = last as u8;
+        iv
+    }
+
+    /// Returns the keystream for a chunk
+    fn keystream(&self, counter: u32, last: bool) -> Aes256Ctr64BE {
+        Aes256Ctr64BE::new(&self.cipher_key.into(), &self.iv(counter, last).into())
+    }
+
+    fn mac(&self, counter: u32, last: bool, ciphertext: &[u8]) -> HmacSha256 {
+        let mut mac = hmac_sha256(&self.mac_key);
+        mac.update(&self.header);
+        mac.update(&self.iv(counter, last)[..NONCE_PREFIX_SIZE + 5]);
+        mac.update(ciphertext);
+        mac
+    }
+
+    fn seal_chunk(&self, counter: u32, last: bool, chunk: &mut [u8]) -> [u8; TAG_SIZE] {
+        self.keystream(counter, last).apply_keystream(chunk);
+        self.mac(counter, last, chunk).finalize().into_bytes().into()
+    }
+
+    /// Checks a chunk's tag without decrypting it
+    fn verify_chunk(&self, counter: u32, last: bool, chunk: &[u8], tag: &[u8]) -> NekoResult<()> {
+        self.mac(counter, last, chunk)
+            .verify_slice(tag)
+            .map_err(|_| NekoError::CryptoError(format!("Authentication failed for chunk {}", counter)))
+    }
+
+    /// Checks a chunk's tag and decrypts it in place
+    fn open_chunk(&self, counter: u32, last: bool, chunk: &mut [u8], tag: &[u8]) -> NekoResult<()> {
+        self.verify_chunk(counter, last, chunk, tag)?;
+        self.keystream(counter, last).apply_keystream(chunk);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for StreamKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamKeys")
+            .field("chunk_size", &self.chunk_size)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Converts a library error into an I/O error for the `Read`/`Write` impls
+fn io_error(err: NekoError) -> io::Error {
+    match err {
+        NekoError::IoError(e) => e,
+        other => io::Error::new(io::ErrorKind::InvalidData, other),
+    }
+}
+
+/// Encrypts everything written to it into an authenticated chunked stream
+///
+/// Call [`Encryptor::finish`] once all data is written; dropping the encryptor
+/// without finishing leaves a stream the [`Decryptor`] rejects as truncated.
+#[derive(Debug)]
+pub struct Encryptor<W: Write> {
+    writer: W,
+    keys: StreamKeys,
+    buffer: Vec<u8>,
+    counter: u32,
+}
+
+impl<W: Write> Encryptor<W> {
+    /// Writes a stream header using [`DEFAULT_CHUNK_SIZE`] chunks
+    pub fn new(writer: W, key: &[u8]) -> NekoResult<Self> {
+        Self::with_chunk_size(writer, key, DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Writes a stream header using chunks of `chunk_size` plaintext bytes
+    pub fn with_chunk_size(mut writer: W, key: &[u8], chunk_size: usize) -> NekoResult<Self> {
+        let keys = StreamKeys::generate(key, chunk_size)?;
+        writer.write_all(&keys.header)?;
+        Ok(Self {
+            writer,
+            keys,
+            buffer: Vec::with_capacity(chunk_size + 1),
+            counter: 0,
+        })
+    }
+
+    /// Seals the buffered data as the final chunk and returns the inner writer
+    pub fn finish(mut self) -> NekoResult<W> {
+        let mut chunk = std::mem::take(&mut self.buffer);
+        self.write_chunk(&mut chunk, true)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_chunk(&mut self, chunk: &mut [u8], last: bool) -> NekoResult<()> {
+        let tag = self.keys.seal_chunk(self.counter, last, chunk);
+        self.writer.write_all(chunk)?;
+        self.writer.write_all(&tag)?;
+        self.counter = self.counter
+            .checked_add(1)
+            .ok_or_else(|| NekoError::InvalidInput("Stream exceeds the maximum number of chunks".into()))?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Encryptor<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let chunk_size = self.keys.chunk_size;
+        let mut written = 0;
+        while written < data.len() {
+            // A full chunk is only sealed once more data arrives, since the
+            // final chunk must carry the last flag
+            if self.buffer.len() == chunk_size {
+                let mut chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(chunk_size + 1));
+                self.write_chunk(&mut chunk, false).map_err(io_error)?;
+            }
+            let take = (chunk_size - self.buffer.len()).min(data.len() - written);
+            self.buffer.extend_from_slice(&data[written..written + take]);
+            written += take;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Decrypts and authenticates a stream produced by [`Encryptor`]
+///
+/// Data is only returned after its chunk has been verified. Reads fail with
+/// [`io::ErrorKind::InvalidData`] if the stream was modified, reordered or
+/// truncated.
+#[derive(Debug)]
+pub struct Decryptor<R: Read> {
+    reader: R,
+    keys: StreamKeys,
+    chunk: Vec<u8>,
+    position: usize,
+    lookahead: Option<u8>,
+    counter: u32,
+    finished: bool,
+}
+
+impl<R: Read> Decryptor<R> {
+    /// Reads and validates the stream header
+    pub fn new(mut reader: R, key: &[u8]) -> NekoResult<Self> {
+        let mut header = [0u8; HEADER_SIZE];
+        reader.read_exact(&mut header).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => NekoError::EncodingError("Truncated stream header".into()),
+            _ => NekoError::IoError(e),
+        })?;
+        let keys = StreamKeys::from_header(header, key)?;
+        Ok(Self {
+            reader,
+            keys,
+            chunk: Vec::new(),
+            position: 0,
+            lookahead: None,
+            counter: 0,
+            finished: false,
+        })
+    }
+
+    /// Returns the inner reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn next_chunk(&mut self) -> NekoResult<()> {
+        let sealed_size = self.keys.chunk_size + TAG_SIZE;
+        let mut sealed = Vec::with_capacity(sealed_size);
+        sealed.extend(self.lookahead.take());
+        (&mut self.reader).take((sealed_size - sealed.len()) as u64).read_to_end(&mut sealed)?;
+
+        // The chunk is final exactly when nothing follows it
+        let mut next = [0u8; 1];
+        let last = loop {
+            match self.reader.read(&mut next) {
+                Ok(0) => break true,
+                Ok(_) => {
+                    self.lookahead = Some(next[0]);
+                    break false;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        if sealed.len() < TAG_SIZE || (!last && sealed.len() < sealed_size) {
+            return Err(NekoError::CryptoError("Truncated stream".into()));
+        }
+
+        let chunk_len = sealed.len() - TAG_SIZE;
+        let (chunk, tag) = sealed.split_at_mut(chunk_len);
+        self.keys.open_chunk(self.counter, last, chunk, tag)?;
+        sealed.truncate(chunk_len);
+
+        self.chunk = sealed;
+        self.position = 0;
+        self.finished = last;
+        self.counter = self.counter
+            .checked_add(1)
+            .ok_or_else(|| NekoError::CryptoError("Stream exceeds the maximum number of chunks".into()))?;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Decryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            if self.finished || buf.is_empty() {
+                return Ok(0);
+            }
+            self.next_chunk().map_err(io_error)?;
+        }
+
+        let n = buf.len().min(self.chunk.len() - self.position);
+        buf[..n].copy_from_slice(&self.chunk[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// Random-access decryption of a stream produced by [`Encryptor`]
+///
+/// Output of [`crate::utils::encrypt_data`] is rejected as not a stream.
+///
+/// Seeking only moves the read position. A read verifies the tag of the
+/// chunk containing the position, then jumps the CTR keystream to the offset
+/// within that chunk and decrypts just the bytes requested, so byte ranges
+/// can be served without processing anything before them.
+#[derive(Debug)]
+pub struct SeekableDecryptor<R: Read + Seek> {
+    reader: R,
+    keys: StreamKeys,
+    start: u64,
+    chunks: u64,
+    last_sealed: usize,
+    len: u64,
+    position: u64,
+    cached: Option<(u64, Vec<u8>)>,
+}
+
+impl<R: Read + Seek> SeekableDecryptor<R> {
+    /// Reads the stream header at the current position and measures the stream
+    pub fn new(mut reader: R, key: &[u8]) -> NekoResult<Self> {
+        let start = reader.stream_position()?;
+        let mut header = [0u8; HEADER_SIZE];
+        reader.read_exact(&mut header).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => NekoError::EncodingError("Truncated stream header".into()),
+            _ => NekoError::IoError(e),
+        })?;
+        let keys = StreamKeys::from_header(header, key)?;
+
+        let body = reader.seek(SeekFrom::End(0))? - start - HEADER_SIZE as u64;
+        let sealed = (keys.chunk_size + TAG_SIZE) as u64;
+        let chunks = body.div_ceil(sealed);
+        let last_sealed = (body - chunks.saturating_sub(1) * sealed) as usize;
+        if chunks == 0 || last_sealed < TAG_SIZE {
+            return Err(NekoError::CryptoError("Truncated stream".into()));
+        }
+        if chunks > u32::MAX as u64 + 1 {
+            return Err(NekoError::EncodingError("Stream exceeds the maximum number of chunks".into()));
+        }
+        let len = (chunks - 1) * keys.chunk_size as u64 + (last_sealed - TAG_SIZE) as u64;
+
+        Ok(Self {
+            reader,
+            keys,
+            start,
+            chunks,
+            last_sealed,
+            len,
+            position: 0,
+            cached: None,
+        })
+    }
+
+    /// Returns the plaintext length in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if the stream holds no plaintext
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the inner reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads and verifies a chunk's ciphertext unless it is already cached
+    fn load_chunk(&mut self, index: u64) -> NekoResult<&[u8]> {
+        if !matches!(&self.cached, Some((cached, _)) if *cached == index) {
+            let sealed = (self.keys.chunk_size + TAG_SIZE) as u64;
+            let last = index == self.chunks - 1;
+            let size = if last { self.last_sealed } else { sealed as usize };
+
+            self.reader.seek(SeekFrom::Start(self.start + HEADER_SIZE as u64 + index * sealed))?;
+            let mut chunk = vec![0u8; size];
+            self.reader.read_exact(&mut chunk)?;
+
+            let tag = chunk.split_off(size - TAG_SIZE);
+            self.keys.verify_chunk(index as u32, last, &chunk, &tag)?;
+            self.cached = Some((index, chunk));
+        }
+        Ok(&self.cached.as_ref().expect("chunk cached above").1)
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableDecryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let chunk_size = self.keys.chunk_size as u64;
+        let index = self.position / chunk_size;
+        let offset = (self.position % chunk_size) as usize;
+        let last = index == self.chunks - 1;
+
+        let chunk = self.load_chunk(index).map_err(io_error)?;
+        let n = buf.len().min(chunk.len() - offset);
+        buf[..n].copy_from_slice(&chunk[offset..offset + n]);
+
+        let mut keystream = self.keys.keystream(index as u32, last);
+        keystream.seek(offset as u64);
+        keystream.apply_keystream(&mut buf[..n]);
+
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableDecryptor<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        self.position = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Seek to a negative or overflowing position")
+        })?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [42; 32];
+
+    fn encrypt(data: &[u8], chunk_size: usize) -> Vec<u8> {
+        let mut encryptor = Encryptor::with_chunk_size(Vec::new(), &KEY, chunk_size).unwrap();
+        // Uneven writes exercise chunk boundaries
+        for piece in data.chunks(7) {
+            encryptor.write_all(piece).unwrap();
+        }
+        encryptor.finish().unwrap()
+    }
+
+    fn decrypt(stream: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        Decryptor::new(stream, &KEY).map_err(io_error)?.read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        for len in [0, 1, 15, 16, 17, 32, 33, 1000] {
+            let stream = encrypt(&data[..len], 16);
+            let chunks = len.div_ceil(16).max(1);
+            assert_eq!(stream.len(), HEADER_SIZE + len + chunks * TAG_SIZE, "len {}", len);
+            assert_eq!(decrypt(&stream).unwrap(), &data[..len]);
+        }
+
+        let stream = encrypt(&data, DEFAULT_CHUNK_SIZE);
+        assert!(stream.starts_with(STREAM_MAGIC));
+        assert_eq!(decrypt(&stream).unwrap(), data);
+    }
+
+    #[test]
+    fn test_wrong_key_and_tampering() {
+        let stream = encrypt(&[7u8; 100], 16);
+
+        let mut out = Vec::new();
+        let mut wrong = Decryptor::new(&stream[..], &[1u8; 32]).unwrap();
+        assert_eq!(wrong.read_to_end(&mut out).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        for i in [8, HEADER_SIZE - 1, HEADER_SIZE, stream.len() - 1] {
+            let mut tampered = stream.clone();
+            tampered[i] ^= 1;
+            assert!(decrypt(&tampered).is_err(), "byte {}", i);
+        }
+    }
+
+    #[test]
+    fn test_truncation_and_reordering() {
+        let stream = encrypt(&[7u8; 64], 16);
+        let sealed = 16 + TAG_SIZE;
+
+        // Dropping whole trailing chunks leaves a non-final chunk last
+        for chunks in 1..4 {
+            assert!(decrypt(&stream[..HEADER_SIZE + chunks * sealed]).is_err());
+        }
+        assert!(decrypt(&stream[..stream.len() - 1]).is_err());
+
+        let mut swapped = stream.clone();
+        let (first, second) = (HEADER_SIZE, HEADER_SIZE + sealed);
+        swapped[first..second].copy_from_slice(&stream[second..second + sealed]);
+        swapped[second..second + sealed].copy_from_slice(&stream[first..second]);
+        assert!(decrypt(&swapped).is_err());
+
+        // Nothing is released from a chunk that fails to verify
+        let mut out = Vec::new();
+        let mut decryptor = Decryptor::new(&swapped[..], &KEY).unwrap();
+        assert!(decryptor.read_to_end(&mut out).is_err());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_seekable_decryptor() {
+        use std::io::Cursor;
+
+        let data: Vec<u8> = (0..200u32).map(|i| (i * 7) as u8).collect();
+        for len in [0, 1, 16, 17, 64, 200] {
+            let stream = encrypt(&data[..len], 16);
+            let mut reader = SeekableDecryptor::new(Cursor::new(stream), &KEY).unwrap();
+            assert_eq!(reader.len(), len as u64);
+
+            let mut out = Vec::new();
+            reader.read_to_end(&mut out).unwrap();
+            assert_eq!(out, &data[..len]);
+        }
+
+        let stream = encrypt(&data, 16);
+        let mut reader = SeekableDecryptor::new(Cursor::new(stream.clone()), &KEY).unwrap();
+        for (start, end) in [(0, 5), (13, 40), (150, 151), (190, 200), (31, 33)] {
+            reader.seek(SeekFrom::Start(start)).unwrap();
+            let mut out = vec![0u8; (end - start) as usize];
+            reader.read_exact(&mut out).unwrap();
+            assert_eq!(out, &data[start as usize..end as usize]);
+        }
+
+        assert_eq!(reader.seek(SeekFrom::End(-3)).unwrap(), 197);
+        assert_eq!(reader.seek(SeekFrom::Current(-7)).unwrap(), 190);
+        assert!(reader.seek(SeekFrom::Current(-191)).is_err());
+        assert_eq!(reader.seek(SeekFrom::End(10)).unwrap(), 210);
+        assert_eq!(reader.read(&mut [0u8; 4]).unwrap(), 0);
+
+        // Streams may start partway into the reader
+        let mut prefixed = b"junk".to_vec();
+        prefixed.extend_from_slice(&stream);
+        let mut cursor = Cursor::new(prefixed);
+        cursor.seek(SeekFrom::Start(4)).unwrap();
+        let mut reader = SeekableDecryptor::new(cursor, &KEY).unwrap();
+        reader.seek(SeekFrom::Start(100)).unwrap();
+        let mut out = [0u8; 10];
+        reader.read_exact(&mut out).unwrap();
+        assert_eq!(out, data[100..110]);
+    }
+
+    #[test]
+    fn test_seekable_decryptor_rejects_tampering() {
+        use std::io::Cursor;
+
+        let stream = encrypt(&[7u8; 64], 16);
+        let sealed = 16 + TAG_SIZE;
+
+        let mut tampered = stream.clone();
+        tampered[HEADER_SIZE + 2 * sealed] ^= 1;
+        let mut reader = SeekableDecryptor::new(Cursor::new(tampered), &KEY).unwrap();
+        let mut out = [0u8; 16];
+        reader.read_exact(&mut out).unwrap();
+        reader.seek(SeekFrom::Start(32)).unwrap();
+        assert_eq!(reader.read(&mut out).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // Truncating at a chunk boundary makes a non-final chunk look final
+        let truncated = stream[..HEADER_SIZE + 2 * sealed].to_vec();
+        let mut reader = SeekableDecryptor::new(Cursor::new(truncated), &KEY).unwrap();
+        reader.seek(SeekFrom::Start(20)).unwrap();
+        assert!(reader.read(&mut out).is_err());
+
+        assert!(SeekableDecryptor::new(Cursor::new(stream[..HEADER_SIZE + 10].to_vec()), &KEY).is_err());
+
+        // Single-tag envelopes are not a seekable format
+        let blob = crate::utils::encrypt_data(&[7u8; 64], Some(&KEY)).unwrap();
+        assert!(matches!(SeekableDecryptor::new(Cursor::new(blob), &KEY), Err(NekoError::EncodingError(_))));
+    }
+
+    #[test]
+    fn test_invalid_headers() {
+        assert!(Encryptor::with_chunk_size(Vec::new(), &KEY, 0).is_err());
+        assert!(Encryptor::new(Vec::new(), &[0u8; 16]).is_err());
+
+        let stream = encrypt(b"data", 16);
+        assert!(matches!(Decryptor::new(&stream[..5], &KEY), Err(NekoError::EncodingError(_))));
+        for (i, value) in [(0, b'X'), (4, 9), (5, 9)] {
+            let mut bad = stream.clone();
+            bad[i] = value;
+            assert!(matches!(Decryptor::new(&bad[..], &KEY), Err(NekoError::EncodingError(_))));
+        }
+        let mut huge = stream.clone();
+        huge[6..10].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Decryptor::new(&huge[..], &KEY).is_err());
+    }
+}
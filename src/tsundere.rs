@@ -1,11 +1,16 @@
-use rand::{Rng, SeedableRng, rngs::StdRng};
-use crate::NekoHash;
+use std::hash::Hasher;
+use crate::{Algorithm, NekoHash, NekoHasher, Version, prng::NekoRng, utils};
+
+const TSUNDERE_SEED: u64 = 0x544e554e44455245; // 0xTSUNDERE
 
 /// TsundereHash implementation with fixed 32-byte output
+#[derive(Clone, Debug)]
 pub struct TsundereHash {
     rounds: usize,
-    state: Vec<u8>,
-    rng: StdRng,
+    version: Version,
+    personalization: Vec<u8>,
+    schedule: NekoRng,
+    key: Option<NekoRng>,
 }
 
 impl Default for TsundereHash {
@@ -22,53 +27,186 @@ impl TsundereHash {
 
     /// Creates a new TsundereHash with specified number of rounds
     pub fn with_rounds(rounds: usize) -> Self {
-        let seed = 0x544e554e44455245; // 0xTSUNDERE
         Self {
             rounds,
-            state: vec![0; 32],
-            rng: StdRng::seed_from_u64(seed),
+            version: Version::V1,
+            personalization: Vec::new(),
+            schedule: NekoRng::from_seed(TSUNDERE_SEED),
+            key: None,
         }
     }
+
+    /// Creates a new TsundereHash with default rounds using the specified algorithm version
+    pub fn with_version(version: Version) -> Self {
+        Self::new().versioned(version)
+    }
+
+    /// Switches this configuration to the specified algorithm version
+    pub fn versioned(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Creates a new TsundereHash with default rounds bound to a personalization string
+    pub fn with_personalization(personalization: &[u8]) -> Self {
+        Self::new().personalized(personalization)
+    }
+
+    /// Binds this configuration to a personalization string
+    ///
+    /// An empty string leaves the configuration unpersonalized.
+    pub fn personalized(mut self, personalization: &[u8]) -> Self {
+        self.personalization = personalization.to_vec();
+        self.schedule = self.derive_schedule();
+        self
+    }
+
+    /// Creates a new keyed v2 TsundereHash with default rounds
+    pub fn with_key(key: &[u8; 32]) -> Self {
+        Self::new().versioned(Version::V2).keyed(key)
+    }
+
+    /// Keys this configuration with a 256-bit secret
+    ///
+    /// The key replaces the built-in seed of the constant schedule, and the
+    /// state starts from 32 bytes drawn from that schedule instead of zeros.
+    ///
+    /// Under [`Version::V1`] the key does not fix the structural collisions
+    /// of the v1 absorption, e.g. `a` and `a\0` still collide, so keyed
+    /// configurations should use [`Version::V2`] as [`TsundereHash::with_key`] does.
+    pub fn keyed(mut self, key: &[u8; 32]) -> Self {
+        self.key = Some(NekoRng::from_key(key));
+        self.schedule = self.derive_schedule();
+        self
+    }
+
+    /// Hashes the input data into a 32-byte array without allocating
+    pub fn hash_array(&self, data: &[u8]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        self.hash_into(data, &mut out);
+        out
+    }
+
+    /// Derives the constant schedule from the key or seed and personalization
+    fn derive_schedule(&self) -> NekoRng {
+        let rng = match &self.key {
+            Some(rng) => rng.clone(),
+            None => NekoRng::from_seed(TSUNDERE_SEED),
+        };
+        if self.personalization.is_empty() {
+            rng
+        } else {
+            rng.personalize(&self.personalization)
+        }
+    }
+}
+
+/// Applies one round of tsundere transformations to the state
+fn round(result: &mut [u8; 32], rng: &mut NekoRng) {
+    // First pass - mix with random values
+    for byte in result.iter_mut() {
+        let random = rng.next_u8();
+        *byte = byte.wrapping_add(random);
+        *byte = byte.rotate_left(3);
+    }
+
+    // Second pass - mix with previous values
+    for i in 1..32 {
+        result[i] ^= result[i - 1];
+    }
+
+    // Third pass - mix with future values
+    for i in (0..31).rev() {
+        result[i] ^= result[i + 1];
+    }
+
+    // Fourth pass - apply tsundere magic
+    for byte in result.iter_mut() {
+        let random = rng.next_u8();
+        *byte = byte.wrapping_mul(0xB5);
+        *byte ^= random;
+    }
 }
 
 impl NekoHash for TsundereHash {
-    fn hash(&self, data: &[u8]) -> Vec<u8> {
-        let mut result = self.state.clone();
-        let mut rng = self.rng.clone();
+    type Hasher = TsundereHasher;
 
-        // Initialize state with input
-        for (i, &byte) in data.iter().enumerate() {
-            result[i % 32] ^= byte;
-        }
+    fn hasher(&self) -> TsundereHasher {
+        let mut hasher = TsundereHasher {
+            rounds: self.rounds,
+            version: self.version,
+            keyed: self.key.is_some(),
+            state: [0; 32],
+            len: 0,
+            rng: self.schedule.clone(),
+            schedule: self.schedule.clone(),
+        };
+        hasher.reset();
+        hasher
+    }
 
-        // Apply tsundere transformations
-        for _ in 0..self.rounds {
-            // First pass - mix with random values
-            for i in 0..32 {
-                let random = rng.gen::<u8>();
-                result[i] = result[i].wrapping_add(random);
-                result[i] = result[i].rotate_left(3);
-            }
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::Tsundere
+    }
 
-            // Second pass - mix with previous values
-            for i in 1..32 {
-                result[i] ^= result[i - 1];
-            }
+    fn version(&self) -> Version {
+        self.version
+    }
 
-            // Third pass - mix with future values
-            for i in (0..31).rev() {
-                result[i] ^= result[i + 1];
-            }
+    fn output_size(&self) -> usize {
+        32
+    }
+}
 
-            // Fourth pass - apply tsundere magic
-            for i in 0..32 {
-                let random = rng.gen::<u8>();
-                result[i] = result[i].wrapping_mul(0xB5);
-                result[i] ^= random;
+/// Incremental TsundereHash state, created by [`TsundereHash::hasher`]
+#[derive(Clone, Debug)]
+pub struct TsundereHasher {
+    rounds: usize,
+    version: Version,
+    keyed: bool,
+    state: [u8; 32],
+    len: u64,
+    rng: NekoRng,
+    schedule: NekoRng,
+}
+
+impl Default for TsundereHasher {
+    fn default() -> Self {
+        TsundereHash::new().hasher()
+    }
+}
+
+impl NekoHasher for TsundereHasher {
+    fn update(&mut self, data: &[u8]) {
+        // Fold input data into the state
+        for &byte in data {
+            let pos = (self.len % 32) as usize;
+            self.state[pos] ^= byte;
+            self.len += 1;
+
+            // V2 runs a round with fresh constants after every block
+            if self.version == Version::V2 && pos == 31 {
+                round(&mut self.state, &mut self.rng);
             }
         }
+    }
+
+    fn finalize_into(mut self, out: &mut [u8]) {
+        assert_eq!(out.len(), 32, "output buffer must match the hash output size");
+
+        if self.version == Version::V2 {
+            // Pad with 0x80 followed by the little-endian message length
+            let len = self.len;
+            self.update(&[0x80]);
+            self.update(&len.to_le_bytes());
+        }
 
-        result
+        // Apply tsundere transformations
+        for _ in 0..self.rounds {
+            round(&mut self.state, &mut self.rng);
+        }
+
+        out.copy_from_slice(&self.state);
     }
 
     fn output_size(&self) -> usize {
@@ -76,8 +214,23 @@ impl NekoHash for TsundereHash {
     }
 
     fn reset(&mut self) {
-        self.state = vec![0; 32];
-        self.rng = StdRng::seed_from_u64(0x544e554e44455245); // 0xTSUNDERE
+        self.state = [0; 32];
+        self.len = 0;
+        self.rng = self.schedule.clone();
+        // Keyed states start from secret bytes drawn ahead of the round constants
+        if self.keyed {
+            self.rng.fill_bytes(&mut self.state);
+        }
+    }
+}
+
+impl Hasher for TsundereHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        utils::digest_to_u64(&self.clone().finalize())
     }
 }
 
@@ -102,6 +255,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_tsundere_hash_reset() {
         let mut hasher = TsundereHash::new();
         let input = b"Hello, World!";
@@ -112,4 +266,144 @@ mod tests {
         
         assert_eq!(hash1, hash2);
     }
+
+    #[test]
+    fn test_tsundere_streaming_matches_oneshot() {
+        let hasher = TsundereHash::new();
+        let input: Vec<u8> = (0..100u8).collect();
+        let expected = hasher.hash(&input);
+
+        let mut state = hasher.hasher();
+        for chunk in input.chunks(7) {
+            state.update(chunk);
+        }
+        assert_eq!(state.finalize(), expected);
+    }
+
+    #[test]
+    fn test_tsundere_hash_array() {
+        let hasher = TsundereHash::new();
+        let input = b"Hello, World!";
+        assert_eq!(hasher.hash_array(input).to_vec(), hasher.hash(input));
+    }
+
+    #[test]
+    fn test_tsundere_std_hasher() {
+        let hasher = TsundereHash::new();
+        let mut state = hasher.hasher();
+        state.write(b"Hello, ");
+        state.write(b"World!");
+
+        let digest = hasher.hash(b"Hello, World!");
+        let expected = u64::from_le_bytes(digest[..8].try_into().unwrap());
+        assert_eq!(state.finish(), expected);
+        // finish() must not consume the state
+        assert_eq!(state.finish(), expected);
+    }
+
+    #[test]
+    fn test_tsundere_golden_vectors() {
+        // Pinned outputs: any change here breaks every stored digest
+        assert_eq!(
+            utils::to_hex(&TsundereHash::new().hash(b"")),
+            "e0ef93dfde2fa648ad6d8ed1d3c094eb1bedf6686c455aa1abf1286fbbdc68b1"
+        );
+        assert_eq!(
+            utils::to_hex(&TsundereHash::new().hash(b"Hello, World!")),
+            "4b99c1d9d4f50b3decefb735fd878499da54e3cd417b38f52c9a60e6e69734d6"
+        );
+        assert_eq!(
+            utils::to_hex(&TsundereHash::with_rounds(3).hash(b"Hello, World!")),
+            "3127358adb20bb46aa84655f707666a29c9c428fda119fc5972c1d9d0c166017"
+        );
+        assert_eq!(
+            utils::to_hex(&TsundereHash::with_version(Version::V2).hash(b"Hello, World!")),
+            "abf7ddcc460c2197f9ffe9bd8a9e79b5ceb1a181aebbd656ad7e947e8952a646"
+        );
+    }
+
+    #[test]
+    fn test_tsundere_v2_absorption() {
+        let v1 = TsundereHash::new();
+        let v2 = TsundereHash::with_version(Version::V2);
+
+        // V1 cannot see trailing zeros, V2 can
+        assert_eq!(v1.hash(b"a"), v1.hash(b"a\0"));
+        assert_ne!(v2.hash(b"a"), v2.hash(b"a\0"));
+        assert_ne!(v2.hash(b""), v2.hash(b"\0"));
+
+        // Swapping whole blocks must change the digest
+        let a = [0x11u8; 32];
+        let b = [0x22u8; 32];
+        assert_ne!(v2.hash(&[a, b].concat()), v2.hash(&[b, a].concat()));
+
+        assert_ne!(v1.hash(b"Hello, World!"), v2.hash(b"Hello, World!"));
+    }
+
+    #[test]
+    fn test_tsundere_v2_streaming_matches_oneshot() {
+        let hasher = TsundereHash::with_version(Version::V2);
+        let input: Vec<u8> = (0..100u8).collect();
+        let expected = hasher.hash(&input);
+
+        for split in [0, 1, 31, 32, 33, 100] {
+            let mut state = hasher.hasher();
+            state.update(&input[..split]);
+            state.update(&input[split..]);
+            assert_eq!(state.finalize(), expected);
+        }
+
+        let mut state = hasher.hasher();
+        state.update(&input);
+        state.reset();
+        state.update(&input);
+        assert_eq!(state.finalize(), expected);
+    }
+
+    #[test]
+    fn test_tsundere_personalization() {
+        let input = b"Hello, World!";
+        let cache = TsundereHash::with_personalization(b"myapp.cache.v1");
+        let files = TsundereHash::with_personalization(b"myapp.files.v1");
+
+        assert_eq!(cache.hash(input), TsundereHash::with_personalization(b"myapp.cache.v1").hash(input));
+        assert_ne!(cache.hash(input), files.hash(input));
+        assert_ne!(cache.hash(input), TsundereHash::new().hash(input));
+        assert_eq!(TsundereHash::with_personalization(b"").hash(input), TsundereHash::new().hash(input));
+
+        let v2 = TsundereHash::with_version(Version::V2).personalized(b"myapp.cache.v1");
+        let mut state = v2.hasher();
+        state.update(&input[..4]);
+        state.update(&input[4..]);
+        assert_eq!(state.finalize(), v2.hash(input));
+    }
+
+    #[test]
+    fn test_tsundere_keyed() {
+        let input = b"Hello, World!";
+        let key: [u8; 32] = std::array::from_fn(|i| 0xA0 + i as u8);
+        let mut other = key;
+        other[0] ^= 1;
+
+        let keyed = TsundereHash::with_key(&key);
+        assert_ne!(keyed.hash(input), TsundereHash::with_key(&other).hash(input));
+        assert_ne!(keyed.hash(input), TsundereHash::with_version(Version::V2).hash(input));
+        assert_ne!(keyed.hash(input), TsundereHash::with_rounds(3).versioned(Version::V2).keyed(&key).hash(input));
+        assert_eq!(
+            TsundereHash::new().keyed(&key).personalized(b"context").hash(input),
+            TsundereHash::new().personalized(b"context").keyed(&key).hash(input)
+        );
+        assert_ne!(keyed.hash(b"a"), keyed.hash(b"a\0"));
+
+        // A keyed state resets to the keyed starting state, not to zeros
+        let mut state = keyed.hasher();
+        state.update(input);
+        state.reset();
+        state.update(input);
+        assert_eq!(state.finalize(), keyed.hash(input));
+
+        let debug = format!("{:?}", keyed);
+        assert!(!debug.contains(&format!("{:?}", key)));
+        assert!(!debug.contains(&utils::to_hex(&key)));
+    }
 }
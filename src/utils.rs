@@ -1,10 +1,8 @@
-use aes::Aes256;
-use ctr::{Ctr64BE, cipher::{KeyIvInit, StreamCipher}};
 use rand::{Rng, thread_rng};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
-use crate::{NekoError, NekoResult, NekoHash, KawaiiHash};
-
-type Aes256Ctr64BE = Ctr64BE<Aes256>;
+use std::time::{Duration, Instant};
+use crate::envelope::Envelope;
+use crate::{NekoError, NekoResult, NekoHash, NekoHasher, KawaiiHash, Version};
 
 /// Converts a byte slice to a hexadecimal string
 #[inline]
@@ -18,9 +16,13 @@ pub fn to_hex(bytes: &[u8]) -> String {
 
 /// Converts a hexadecimal string to a byte vector
 pub fn from_hex(hex: &str) -> NekoResult<Vec<u8>> {
-    if hex.len() % 2 != 0 {
+    if !hex.len().is_multiple_of(2) {
         return Err(NekoError::EncodingError("Invalid hex string length".into()));
     }
+    // Slicing below is by byte index, which is only safe for ASCII
+    if !hex.is_ascii() {
+        return Err(NekoError::EncodingError("Invalid hex character: non-ASCII input".into()));
+    }
 
     let mut bytes = Vec::with_capacity(hex.len() / 2);
     for i in (0..hex.len()).step_by(2) {
@@ -52,54 +54,50 @@ pub fn combine_hashes(hashes: &[Vec<u8>]) -> Vec<u8> {
     result
 }
 
-/// Encrypts data using AES-256-CTR with either a provided key or a random key
+/// Encrypts and authenticates data with either a provided key or a random key
+///
+/// Equivalent to [`encrypt_data_with_aad`] with empty associated data.
 pub fn encrypt_data(data: &[u8], key: Option<&[u8]>) -> NekoResult<Vec<u8>> {
-    let mut rng = thread_rng();
-    
-    let key = match key {
-        Some(k) if k.len() == 32 => k.to_vec(),
-        Some(_) => return Err(NekoError::KeyError("Key must be exactly 32 bytes".into())),
-        None => {
-            let mut key = vec![0u8; 32];
-            rng.fill(&mut key[..]);
-            key
-        }
-    };
-
-    let mut iv = [0u8; 16];
-    rng.fill(&mut iv[..]);
+    encrypt_data_with_aad(data, &[], key)
+}
 
-    let mut cipher = Aes256Ctr64BE::new(key[..].into(), &iv.into());
-    let mut buf = data.to_vec();
-    cipher.apply_keystream(&mut buf);
+/// Decrypts data produced by [`encrypt_data`], failing if it was tampered with
+pub fn decrypt_data(encrypted_data: &[u8], key: &[u8]) -> NekoResult<Vec<u8>> {
+    decrypt_data_with_aad(encrypted_data, &[], key)
+}
 
-    let mut result = Vec::with_capacity(16 + buf.len());
-    result.extend_from_slice(&iv);
-    result.extend_from_slice(&buf);
-    
-    Ok(BASE64.encode(result).into_bytes())
+/// Encrypts data and authenticates it together with `aad`
+///
+/// Output is base64 of a binary [`Envelope`] sealed with AES-256-CTR and
+/// HMAC-SHA256 and an empty key id.
+pub fn encrypt_data_with_aad(data: &[u8], aad: &[u8], key: Option<&[u8]>) -> NekoResult<Vec<u8>> {
+    let key = match key {
+        Some(k) => k.to_vec(),
+        None => generate_key(),
+    };
+    let envelope = Envelope::seal(data, aad, &key, &[])?;
+    Ok(BASE64.encode(envelope.to_bytes()).into_bytes())
 }
 
-/// Decrypts data using AES-256-CTR with the provided key
-pub fn decrypt_data(encrypted_data: &[u8], key: &[u8]) -> NekoResult<Vec<u8>> {
+/// Decrypts data produced by [`encrypt_data_with_aad`]
+///
+/// Accepts the base64 envelope or its ASCII armor, dispatching on the
+/// envelope's version and cipher suite. Returns [`NekoError::CryptoError`] if
+/// the key or `aad` is wrong or the ciphertext was modified.
+pub fn decrypt_data_with_aad(encrypted_data: &[u8], aad: &[u8], key: &[u8]) -> NekoResult<Vec<u8>> {
     if key.len() != 32 {
         return Err(NekoError::KeyError("Key must be exactly 32 bytes".into()));
     }
 
-    let encrypted = BASE64.decode(encrypted_data)
-        .map_err(|e| NekoError::EncodingError(format!("Invalid base64: {}", e)))?;
-
-    if encrypted.len() < 16 {
-        return Err(NekoError::CryptoError("Invalid encrypted data".into()));
-    }
-
-    let (iv, ciphertext) = encrypted.split_at(16);
-    
-    let mut cipher = Aes256Ctr64BE::new(key.into(), iv.into());
-    let mut buf = ciphertext.to_vec();
-    cipher.apply_keystream(&mut buf);
-
-    Ok(buf)
+    let envelope = match std::str::from_utf8(encrypted_data) {
+        Ok(text) if text.trim_start().starts_with("-----BEGIN") => Envelope::from_armor(text)?,
+        _ => {
+            let bytes = BASE64.decode(encrypted_data)
+                .map_err(|e| NekoError::EncodingError(format!("Invalid base64: {}", e)))?;
+            Envelope::from_bytes(&bytes)?
+        }
+    };
+    envelope.open(aad, key)
 }
 
 /// Generates a random encryption key
@@ -122,6 +120,15 @@ pub fn key_from_base64(key_str: &str) -> NekoResult<Vec<u8>> {
         .map_err(|e| NekoError::EncodingError(format!("Invalid base64 key: {}", e)))
 }
 
+/// Reads the leading bytes of a digest as a little-endian `u64`,
+/// zero-padding digests shorter than eight bytes
+pub(crate) fn digest_to_u64(digest: &[u8]) -> u64 {
+    let mut bytes = [0u8; 8];
+    let len = digest.len().min(8);
+    bytes[..len].copy_from_slice(&digest[..len]);
+    u64::from_le_bytes(bytes)
+}
+
 /// Constant-time comparison of two byte slices
 /// Useful for comparing hashes without timing attacks
 #[inline]
@@ -157,8 +164,31 @@ pub fn stretch_key(data: &[u8], iterations: usize, output_size: usize) -> NekoRe
     Ok(result)
 }
 
+/// Cost parameters for [`derive_key_with_params`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyDerivationParams {
+    /// Number of KawaiiHash iterations
+    pub iterations: usize,
+    /// Output size in bytes
+    pub output_size: usize,
+}
+
+impl Default for KeyDerivationParams {
+    fn default() -> Self {
+        Self {
+            iterations: 10000,
+            output_size: 32,
+        }
+    }
+}
+
 /// Generates a deterministic key from a password and salt
 pub fn derive_key(password: &[u8], salt: &[u8]) -> NekoResult<Vec<u8>> {
+    derive_key_with_params(password, salt, &KeyDerivationParams::default())
+}
+
+/// Generates a deterministic key from a password and salt with explicit cost parameters
+pub fn derive_key_with_params(password: &[u8], salt: &[u8], params: &KeyDerivationParams) -> NekoResult<Vec<u8>> {
     if password.is_empty() {
         return Err(NekoError::InvalidInput("Password cannot be empty".into()));
     }
@@ -170,7 +200,290 @@ pub fn derive_key(password: &[u8], salt: &[u8]) -> NekoResult<Vec<u8>> {
     input.extend_from_slice(password);
     input.extend_from_slice(salt);
     
-    stretch_key(&input, 10000, 32)
+    stretch_key(&input, params.iterations, params.output_size)
+}
+
+/// Benchmarks `stretch_key` on this machine and returns the iteration count
+/// that takes roughly `target` to derive a 32-byte key
+///
+/// Spends at most about 25ms measuring, so the result is an estimate and
+/// should be stored alongside derived keys rather than recomputed.
+pub fn calibrate_iterations(target: Duration) -> usize {
+    let window = target.min(Duration::from_millis(25));
+    if window.is_zero() {
+        return 1;
+    }
+
+    let mut probe = 1usize;
+    let elapsed = loop {
+        let start = Instant::now();
+        // Cannot fail: probe and output size are both non-zero
+        let _ = stretch_key(b"nekohash.calibration", probe, 32);
+        let elapsed = start.elapsed();
+        if elapsed >= window || probe >= usize::MAX / 2 {
+            break elapsed;
+        }
+        probe *= 2;
+    };
+
+    let per_iteration = elapsed.as_secs_f64() / probe as f64;
+    let iterations = target.as_secs_f64() / per_iteration.max(f64::MIN_POSITIVE);
+    (iterations as usize).max(1)
+}
+
+/// Cost parameters for [`memory_hard_key`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryHardParams {
+    /// Total memory to fill, in KiB
+    pub memory_kib: usize,
+    /// Number of passes over the memory
+    pub time_cost: usize,
+    /// Number of independent lanes (at most 64), computed on separate threads
+    pub parallelism: usize,
+    /// Output size in bytes
+    pub output_size: usize,
+}
+
+impl Default for MemoryHardParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: 16 * 1024,
+            time_cost: 3,
+            parallelism: 1,
+            output_size: 32,
+        }
+    }
+}
+
+const MEMORY_BLOCK_SIZE: usize = 64;
+
+/// Largest lane count accepted by [`memory_hard_key`], which spawns one
+/// thread per lane
+const MAX_PARALLELISM: usize = 64;
+
+/// Derives a key from a password and salt with a memory-hard function
+///
+/// Each lane fills `memory_kib / parallelism` KiB with 64-byte blocks, each
+/// the v2 KawaiiHash of its predecessor XORed with an earlier block chosen by
+/// the predecessor's contents. Further passes revisit every block the same
+/// way, so computing the key cheaply requires keeping all of memory around.
+pub fn memory_hard_key(password: &[u8], salt: &[u8], params: &MemoryHardParams) -> NekoResult<Vec<u8>> {
+    if password.is_empty() {
+        return Err(NekoError::InvalidInput("Password cannot be empty".into()));
+    }
+    if salt.is_empty() {
+        return Err(NekoError::InvalidInput("Salt cannot be empty".into()));
+    }
+    if params.time_cost == 0 {
+        return Err(NekoError::InvalidInput("Time cost must be greater than 0".into()));
+    }
+    if params.parallelism == 0 {
+        return Err(NekoError::InvalidInput("Parallelism must be greater than 0".into()));
+    }
+    if params.parallelism > MAX_PARALLELISM {
+        return Err(NekoError::InvalidInput(format!("Parallelism must be at most {}", MAX_PARALLELISM)));
+    }
+    if params.output_size == 0 {
+        return Err(NekoError::InvalidInput("Output size must be greater than 0".into()));
+    }
+    let lane_blocks = params.memory_kib.saturating_mul(1024 / MEMORY_BLOCK_SIZE) / params.parallelism;
+    if lane_blocks < 2 {
+        return Err(NekoError::InvalidInput("Memory must hold at least two blocks per lane".into()));
+    }
+
+    let block_hash = KawaiiHash::with_size(MEMORY_BLOCK_SIZE).versioned(Version::V2);
+
+    // Bind every input and parameter into the initial seed
+    let mut seed = [0u8; MEMORY_BLOCK_SIZE];
+    let mut hasher = block_hash.hasher();
+    hasher.update(b"nekohash.memory-hard");
+    for value in [params.memory_kib, params.time_cost, params.parallelism, params.output_size] {
+        hasher.update(&(value as u64).to_le_bytes());
+    }
+    hasher.update(&(password.len() as u64).to_le_bytes());
+    hasher.update(password);
+    hasher.update(&(salt.len() as u64).to_le_bytes());
+    hasher.update(salt);
+    hasher.finalize_into(&mut seed);
+
+    let fill_lane = |lane: usize| -> [u8; MEMORY_BLOCK_SIZE] {
+        let mut memory = vec![[0u8; MEMORY_BLOCK_SIZE]; lane_blocks];
+        for (index, block) in memory.iter_mut().take(2).enumerate() {
+            let mut input = Vec::with_capacity(MEMORY_BLOCK_SIZE + 16);
+            input.extend_from_slice(&seed);
+            input.extend_from_slice(&(lane as u64).to_le_bytes());
+            input.extend_from_slice(&(index as u64).to_le_bytes());
+            block_hash.hash_into(&input, block);
+        }
+
+        let mut mixed = [0u8; MEMORY_BLOCK_SIZE];
+        for pass in 0..params.time_cost {
+            let start = if pass == 0 { 2 } else { 0 };
+            for index in start..lane_blocks {
+                let prev = memory[(index + lane_blocks - 1) % lane_blocks];
+                // First pass may only reference blocks that are already filled
+                let window = if pass == 0 { index } else { lane_blocks };
+                let reference = (digest_to_u64(&prev) % window as u64) as usize;
+
+                for (m, (p, r)) in mixed.iter_mut().zip(prev.iter().zip(memory[reference].iter())) {
+                    *m = p ^ r;
+                }
+                let mut block = [0u8; MEMORY_BLOCK_SIZE];
+                block_hash.hash_into(&mixed, &mut block);
+                if pass > 0 {
+                    for (b, old) in block.iter_mut().zip(memory[index].iter()) {
+                        *b ^= old;
+                    }
+                }
+                memory[index] = block;
+            }
+        }
+
+        memory[lane_blocks - 1]
+    };
+
+    let lanes: Vec<[u8; MEMORY_BLOCK_SIZE]> = if params.parallelism == 1 {
+        vec![fill_lane(0)]
+    } else {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..params.parallelism)
+                .map(|lane| scope.spawn(move || fill_lane(lane)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join())
+                .collect::<Result<_, _>>()
+        })
+        .map_err(|_| NekoError::HashError("Memory-hard lane panicked".into()))?
+    };
+
+    let mut last = [0u8; MEMORY_BLOCK_SIZE];
+    for lane in &lanes {
+        for (l, b) in last.iter_mut().zip(lane.iter()) {
+            *l ^= b;
+        }
+    }
+
+    let mut hasher = KawaiiHash::with_size(params.output_size).versioned(Version::V2).hasher();
+    hasher.update(&seed);
+    hasher.update(&last);
+    Ok(hasher.finalize())
+}
+
+const PASSWORD_MAGIC: &[u8; 6] = b"NEKOPW";
+const PASSWORD_FORMAT_VERSION: u8 = 1;
+const PASSWORD_KDF_MEMORY_HARD: u8 = 1;
+const PASSWORD_HEADER_SIZE: usize = 6 + 1 + 1 + 12 + 1;
+
+/// Largest memory cost accepted when decrypting, so a crafted blob cannot
+/// make [`decrypt_with_password`] allocate without bound
+const MAX_PASSWORD_MEMORY_KIB: usize = 64 * 1024;
+
+/// Largest number of passes accepted when decrypting
+const MAX_PASSWORD_TIME_COST: usize = 4;
+
+/// Largest number of lanes, and so threads, accepted when decrypting
+const MAX_PASSWORD_PARALLELISM: usize = 4;
+
+/// Rejects KDF costs that [`decrypt_with_password`] would refuse
+fn check_password_params(params: &MemoryHardParams) -> NekoResult<()> {
+    if params.memory_kib > MAX_PASSWORD_MEMORY_KIB {
+        return Err(NekoError::InvalidInput("KDF memory cost exceeds the supported maximum".into()));
+    }
+    if params.time_cost > MAX_PASSWORD_TIME_COST {
+        return Err(NekoError::InvalidInput("KDF time cost exceeds the supported maximum".into()));
+    }
+    if params.parallelism > MAX_PASSWORD_PARALLELISM {
+        return Err(NekoError::InvalidInput("KDF parallelism exceeds the supported maximum".into()));
+    }
+    Ok(())
+}
+
+/// Encrypts data under a passphrase with the default [`MemoryHardParams`]
+///
+/// The salt and KDF parameters are stored in the output, so only the
+/// password is needed to decrypt.
+pub fn encrypt_with_password(data: &[u8], password: &[u8]) -> NekoResult<Vec<u8>> {
+    encrypt_with_password_params(data, password, &MemoryHardParams::default())
+}
+
+/// Encrypts data under a passphrase with explicit KDF cost parameters
+///
+/// Output is base64 of a header (`NEKOPW` magic, format version, KDF id,
+/// memory, time and parallelism costs, salt) followed by an [`Envelope`]
+/// whose tag authenticates the header. `params.output_size` is ignored;
+/// the key is always 32 bytes.
+///
+/// Costs above the limits enforced by [`decrypt_with_password`] are rejected
+/// with [`NekoError::InvalidInput`].
+pub fn encrypt_with_password_params(data: &[u8], password: &[u8], params: &MemoryHardParams) -> NekoResult<Vec<u8>> {
+    let params = MemoryHardParams { output_size: 32, ..*params };
+    check_password_params(&params)?;
+    let salt = generate_salt();
+
+    let cost = |value: usize| -> NekoResult<[u8; 4]> {
+        u32::try_from(value)
+            .map(u32::to_le_bytes)
+            .map_err(|_| NekoError::InvalidInput("KDF parameter does not fit in 32 bits".into()))
+    };
+    let mut header = Vec::with_capacity(PASSWORD_HEADER_SIZE + salt.len());
+    header.extend_from_slice(PASSWORD_MAGIC);
+    header.push(PASSWORD_FORMAT_VERSION);
+    header.push(PASSWORD_KDF_MEMORY_HARD);
+    header.extend_from_slice(&cost(params.memory_kib)?);
+    header.extend_from_slice(&cost(params.time_cost)?);
+    header.extend_from_slice(&cost(params.parallelism)?);
+    header.push(salt.len() as u8);
+    header.extend_from_slice(&salt);
+
+    let key = memory_hard_key(password, &salt, &params)?;
+    let envelope = Envelope::seal(data, &header, &key, &[])?;
+
+    let mut blob = header;
+    blob.extend_from_slice(&envelope.to_bytes());
+    Ok(BASE64.encode(blob).into_bytes())
+}
+
+/// Decrypts data produced by [`encrypt_with_password`]
+///
+/// Returns [`NekoError::CryptoError`] if the password is wrong or the data
+/// was modified.
+pub fn decrypt_with_password(encrypted_data: &[u8], password: &[u8]) -> NekoResult<Vec<u8>> {
+    let blob = BASE64.decode(encrypted_data)
+        .map_err(|e| NekoError::EncodingError(format!("Invalid base64: {}", e)))?;
+
+    let rest = blob
+        .strip_prefix(PASSWORD_MAGIC.as_slice())
+        .ok_or_else(|| NekoError::EncodingError("Not password-encrypted data".into()))?;
+    if blob.len() < PASSWORD_HEADER_SIZE {
+        return Err(NekoError::EncodingError("Truncated password header".into()));
+    }
+    if rest[0] != PASSWORD_FORMAT_VERSION {
+        return Err(NekoError::EncodingError(format!("Unsupported password format version: {}", rest[0])));
+    }
+    if rest[1] != PASSWORD_KDF_MEMORY_HARD {
+        return Err(NekoError::EncodingError(format!("Unsupported password KDF: {}", rest[1])));
+    }
+
+    let cost = |i: usize| u32::from_le_bytes([rest[i], rest[i + 1], rest[i + 2], rest[i + 3]]) as usize;
+    let params = MemoryHardParams {
+        memory_kib: cost(2),
+        time_cost: cost(6),
+        parallelism: cost(10),
+        output_size: 32,
+    };
+    check_password_params(&params)?;
+
+    let salt_len = rest[14] as usize;
+    if blob.len() < PASSWORD_HEADER_SIZE + salt_len {
+        return Err(NekoError::EncodingError("Truncated password header".into()));
+    }
+    let (header, envelope) = blob.split_at(PASSWORD_HEADER_SIZE + salt_len);
+    let salt = &header[PASSWORD_HEADER_SIZE..];
+
+    let envelope = Envelope::from_bytes(envelope)?;
+    let key = memory_hard_key(password, salt, &params)?;
+    envelope.open(header, &key)
 }
 
 /// Generates a cryptographically secure random salt
@@ -275,11 +588,129 @@ mod tests {
         assert!(derive_key(password, &[]).is_err());
     }
 
+    #[test]
+    fn test_derive_key_with_params() {
+        let default = derive_key_with_params(b"password123", b"saltsalt", &KeyDerivationParams::default()).unwrap();
+        assert_eq!(default, derive_key(b"password123", b"saltsalt").unwrap());
+
+        let params = KeyDerivationParams { iterations: 50, output_size: 48 };
+        let key = derive_key_with_params(b"password123", b"saltsalt", &params).unwrap();
+        assert_eq!(key, stretch_key(b"password123saltsalt", 50, 48).unwrap());
+
+        let zero = KeyDerivationParams { iterations: 0, ..params };
+        assert!(derive_key_with_params(b"password123", b"saltsalt", &zero).is_err());
+        assert!(derive_key_with_params(&[], b"saltsalt", &params).is_err());
+    }
+
+    #[test]
+    fn test_calibrate_iterations() {
+        assert_eq!(calibrate_iterations(Duration::ZERO), 1);
+
+        let iterations = calibrate_iterations(Duration::from_millis(10));
+        assert!(iterations >= 1);
+        let params = KeyDerivationParams { iterations, ..KeyDerivationParams::default() };
+        assert_eq!(derive_key_with_params(b"password123", b"saltsalt", &params).unwrap().len(), 32);
+    }
+
+    #[test]
+    fn test_password_encryption() {
+        let params = MemoryHardParams { memory_kib: 32, time_cost: 1, ..MemoryHardParams::default() };
+        let encrypted = encrypt_with_password_params(b"test data", b"hunter2", &params).unwrap();
+        assert_eq!(decrypt_with_password(&encrypted, b"hunter2").unwrap(), b"test data");
+        assert!(matches!(decrypt_with_password(&encrypted, b"hunter3"), Err(NekoError::CryptoError(_))));
+
+        // Fresh salt every time
+        assert_ne!(encrypted, encrypt_with_password_params(b"test data", b"hunter2", &params).unwrap());
+
+        // Parameters live in the authenticated header
+        let mut raw = BASE64.decode(&encrypted).unwrap();
+        raw[8] ^= 1;
+        let tampered = BASE64.encode(&raw).into_bytes();
+        assert!(matches!(decrypt_with_password(&tampered, b"hunter2"), Err(NekoError::CryptoError(_))));
+
+        for (offset, cost) in [(8, MAX_PASSWORD_MEMORY_KIB), (12, MAX_PASSWORD_TIME_COST), (16, MAX_PASSWORD_PARALLELISM)] {
+            let mut raw = BASE64.decode(&encrypted).unwrap();
+            raw[offset..offset + 4].copy_from_slice(&(cost as u32 + 1).to_le_bytes());
+            let greedy = BASE64.encode(&raw).into_bytes();
+            assert!(matches!(decrypt_with_password(&greedy, b"hunter2"), Err(NekoError::InvalidInput(_))));
+        }
+        let slow = MemoryHardParams { time_cost: MAX_PASSWORD_TIME_COST + 1, ..params };
+        assert!(matches!(encrypt_with_password_params(b"test data", b"hunter2", &slow), Err(NekoError::InvalidInput(_))));
+
+        assert!(decrypt_with_password(&encrypt_data(b"test data", None).unwrap(), b"hunter2").is_err());
+        assert!(decrypt_with_password(&BASE64.encode(b"NEKOPW\x01").into_bytes(), b"hunter2").is_err());
+        assert!(encrypt_with_password_params(b"test data", b"", &params).is_err());
+    }
+
+    #[test]
+    fn test_password_header_rejected_before_kdf() {
+        // A bare header with no salt or envelope: anything that got past the
+        // cost check would fail to parse the envelope with an EncodingError
+        let header = |memory_kib: usize, time_cost: usize| {
+            let mut raw = PASSWORD_MAGIC.to_vec();
+            raw.extend_from_slice(&[PASSWORD_FORMAT_VERSION, PASSWORD_KDF_MEMORY_HARD]);
+            raw.extend_from_slice(&(memory_kib as u32).to_le_bytes());
+            raw.extend_from_slice(&(time_cost as u32).to_le_bytes());
+            raw.extend_from_slice(&1u32.to_le_bytes());
+            raw.push(0);
+            BASE64.encode(&raw).into_bytes()
+        };
+
+        assert!(matches!(decrypt_with_password(&header(32, 1), b"hunter2"), Err(NekoError::EncodingError(_))));
+        assert!(matches!(
+            decrypt_with_password(&header(u32::MAX as usize, 1), b"hunter2"),
+            Err(NekoError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            decrypt_with_password(&header(32, MAX_PASSWORD_TIME_COST + 1), b"hunter2"),
+            Err(NekoError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_memory_hard_key() {
+        let params = MemoryHardParams {
+            memory_kib: 32,
+            time_cost: 2,
+            parallelism: 1,
+            output_size: 32,
+        };
+        let key1 = memory_hard_key(b"password123", b"saltsalt", &params).unwrap();
+        let key2 = memory_hard_key(b"password123", b"saltsalt", &params).unwrap();
+        assert_eq!(key1.len(), 32);
+        assert_eq!(key1, key2);
+
+        assert_ne!(key1, memory_hard_key(b"password124", b"saltsalt", &params).unwrap());
+        assert_ne!(key1, memory_hard_key(b"password123", b"saltsalu", &params).unwrap());
+        for changed in [
+            MemoryHardParams { memory_kib: 16, ..params },
+            MemoryHardParams { time_cost: 1, ..params },
+            MemoryHardParams { parallelism: 2, ..params },
+        ] {
+            assert_ne!(key1, memory_hard_key(b"password123", b"saltsalt", &changed).unwrap());
+        }
+
+        let long = MemoryHardParams { output_size: 100, ..params };
+        assert_eq!(memory_hard_key(b"password123", b"saltsalt", &long).unwrap().len(), 100);
+
+        assert!(memory_hard_key(&[], b"saltsalt", &params).is_err());
+        assert!(memory_hard_key(b"password123", &[], &params).is_err());
+        assert!(memory_hard_key(b"password123", b"saltsalt", &MemoryHardParams { time_cost: 0, ..params }).is_err());
+        assert!(memory_hard_key(b"password123", b"saltsalt", &MemoryHardParams { parallelism: 0, ..params }).is_err());
+        let too_parallel = MemoryHardParams { memory_kib: 1024 * 1024, parallelism: MAX_PARALLELISM + 1, ..params };
+        assert!(matches!(
+            memory_hard_key(b"password123", b"saltsalt", &too_parallel),
+            Err(NekoError::InvalidInput(_))
+        ));
+        assert!(memory_hard_key(b"password123", b"saltsalt", &MemoryHardParams { output_size: 0, ..params }).is_err());
+        assert!(memory_hard_key(b"password123", b"saltsalt", &MemoryHardParams { memory_kib: 0, ..params }).is_err());
+    }
+
     #[test]
     fn test_time_based_key() {
         let seed = b"test_seed";
         let key1 = time_based_key(seed, 30).unwrap();
-        let key2 = time_based_key(seed, 30).unwrap();
+        let _key2 = time_based_key(seed, 30).unwrap();
         
         assert_eq!(key1.len(), 32);
         assert!(time_based_key(&[], 30).is_err());
@@ -316,6 +747,43 @@ mod tests {
         
         let wrong_key = generate_key();
         let wrong_decrypted = decrypt_data(&encrypted, &wrong_key);
-        assert!(wrong_decrypted.is_ok() && wrong_decrypted.unwrap() != data);
+        assert!(matches!(wrong_decrypted, Err(NekoError::CryptoError(_))));
+    }
+
+    #[test]
+    fn test_decrypt_armored() {
+        let key = generate_key();
+        let envelope = Envelope::seal(b"test data", b"", &key, b"k1").unwrap();
+        assert_eq!(decrypt_data(envelope.to_armor().as_bytes(), &key).unwrap(), b"test data");
+    }
+
+    #[test]
+    fn test_encryption_with_aad() {
+        let key = generate_key();
+        let encrypted = encrypt_data_with_aad(b"test data", b"user:42", Some(&key)).unwrap();
+        assert_eq!(decrypt_data_with_aad(&encrypted, b"user:42", &key).unwrap(), b"test data");
+
+        assert!(matches!(decrypt_data_with_aad(&encrypted, b"user:43", &key), Err(NekoError::CryptoError(_))));
+        assert!(matches!(decrypt_data(&encrypted, &key), Err(NekoError::CryptoError(_))));
+    }
+
+    #[test]
+    fn test_encryption_detects_tampering() {
+        let key = generate_key();
+        let encrypted = encrypt_data(b"test data", Some(&key)).unwrap();
+        let raw = BASE64.decode(&encrypted).unwrap();
+        assert!(raw.starts_with(crate::envelope::MAGIC));
+
+        let mut tampered = raw.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        let tampered = BASE64.encode(tampered).into_bytes();
+        assert!(matches!(decrypt_data(&tampered, &key), Err(NekoError::CryptoError(_))));
+
+        let truncated = BASE64.encode(&raw[..raw.len() - 10]).into_bytes();
+        assert!(matches!(decrypt_data(&truncated, &key), Err(NekoError::EncodingError(_))));
+
+        // Empty plaintext still carries a tag
+        let empty = encrypt_data(b"", Some(&key)).unwrap();
+        assert_eq!(decrypt_data(&empty, &key).unwrap(), b"");
     }
 }